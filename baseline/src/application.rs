@@ -1,10 +1,16 @@
 use std::collections::HashMap;
 
-use crate::{Category, Event, Id, ProcessId, Scope, ThreadId, CCT};
+use crate::{
+    churn_by_type, CCTNode, Category, ClockSync, CounterValues, Counters, Event, FlowGraph, Id,
+    InstantScope, MemoryDiff, MemoryDump, MemoryTimeline, Metadata, NodeRef, ObjectHistory,
+    ProcessId, Profiles, Scope, ThreadId, Timestamp, TraceFields, TypeChurn, WebVitals, CCT,
+};
 
 pub type SyncTaskId = (ProcessId, ThreadId);
-type AsyncTaskId = (Scope, Id, Category);
-type ObjectLifeCycleId = (Scope, Id);
+/// async tasks and objects are told apart by their ids, where local ids are qualified by the pid
+/// of their process.
+type AsyncTaskId = (Scope, Option<ProcessId>, Id, Category);
+pub type ObjectLifeCycleId = (Scope, Option<ProcessId>, Id);
 
 /// ApplicationTrace is a middle stage that holds a series of vectors of events,
 /// each later used to construct a new CCT.
@@ -13,6 +19,17 @@ pub struct ApplicationTrace {
     pub sync_tasks: HashMap<SyncTaskId, Vec<Event>>,
     pub async_tasks: HashMap<AsyncTaskId, Vec<Event>>,
     pub object_life_cycle: HashMap<ObjectLifeCycleId, Vec<Event>>,
+    pub markers: Markers,
+    pub metadata: Metadata,
+    pub counters: Counters,
+    /// the sample events, which are resolved along with the `samples` of the fields.
+    pub samples: Vec<Event>,
+    pub memory: MemoryTimeline,
+    /// the offsets the timestamps of the processes were moved by.
+    pub clock_sync: ClockSync,
+    /// the flow events, which are resolved once every sync CCT is built.
+    pub flows: Vec<Event>,
+    pub fields: TraceFields,
 }
 
 impl ApplicationTrace {
@@ -20,6 +37,19 @@ impl ApplicationTrace {
         Default::default()
     }
 
+    /// puts the events of every task in order of time, keeping the order of the events at the
+    /// same time.
+    pub fn sort(&mut self) {
+        let tasks = self
+            .sync_tasks
+            .values_mut()
+            .chain(self.async_tasks.values_mut())
+            .chain(self.object_life_cycle.values_mut());
+        for events in tasks {
+            events.sort();
+        }
+    }
+
     pub fn application_cct(self) -> ApplicationCCT {
         let mut app_cct = ApplicationCCT {
             ..Default::default()
@@ -35,6 +65,23 @@ impl ApplicationTrace {
                 .object_life_cycle
                 .insert(object_life_cycle_id, CCT::from(events));
         }
+        app_cct.fields = self.fields;
+        app_cct.markers = self.markers;
+        app_cct.markers.sort();
+        app_cct.metadata = self.metadata;
+        app_cct.name_sync_tasks();
+        app_cct.counters = self.counters;
+        app_cct.counters.sort();
+        app_cct.memory = self.memory;
+        app_cct.memory.sort();
+        app_cct.clock_sync = self.clock_sync;
+        app_cct.web_vitals = WebVitals::from_sync_tasks(app_cct.sync_tasks.values());
+        app_cct.flows = FlowGraph::resolve(self.flows, &app_cct.sync_tasks);
+        let threads = app_cct.threads();
+        app_cct
+            .clock_sync
+            .rebase_samples(&mut app_cct.fields, &threads);
+        app_cct.profiles = Profiles::resolve(self.samples, &app_cct.fields, &threads);
         app_cct
     }
 }
@@ -47,4 +94,180 @@ pub struct ApplicationCCT {
     pub sync_tasks: HashMap<SyncTaskId, CCT>,
     pub async_tasks: HashMap<AsyncTaskId, CCT>,
     pub object_life_cycle: HashMap<ObjectLifeCycleId, CCT>,
+    pub markers: Markers,
+    /// the names, sort indexes, labels and uptimes of the processes and threads.
+    pub metadata: Metadata,
+    /// the counters of every process as time series.
+    pub counters: Counters,
+    /// the sampled CCTs of the threads.
+    pub profiles: Profiles,
+    /// the memory dumps of every process over time.
+    pub memory: MemoryTimeline,
+    /// the estimated skews of the clocks of the processes, which are already taken out of the
+    /// timestamps.
+    pub clock_sync: ClockSync,
+    /// the page loads and their milestones.
+    pub web_vitals: WebVitals,
+    /// the flows between the nodes of the sync CCTs.
+    pub flows: FlowGraph,
+    /// the fields of the trace object besides its events.
+    pub fields: TraceFields,
+}
+
+impl ApplicationCCT {
+    /// returns the node of a sync CCT that a flow points to.
+    pub fn node(&self, node: NodeRef) -> Option<&CCTNode> {
+        self.sync_tasks.get(&node.task)?.node(node.node)
+    }
+
+    /// what the counters of the process of a node read at its start and its stop.
+    pub fn counters_at(&self, node: &CCTNode) -> Vec<CounterValues<'_>> {
+        self.counters.at_node(node.event().pid, node)
+    }
+
+    /// the memory dump of the process of a node that is nearest to it.
+    pub fn memory_dump_near(&self, node: &CCTNode) -> Option<&MemoryDump> {
+        let stop = node.stop_time().unwrap_or(node.start_time());
+        self.memory
+            .nearest(node.event().pid, node.start_time(), stop)
+    }
+
+    /// how the memory of the process of a node changed from the dump before it to the dump
+    /// after it.
+    pub fn memory_diff_over(&self, node: &CCTNode) -> Option<MemoryDiff> {
+        let stop = node.stop_time().unwrap_or(node.start_time());
+        self.memory
+            .diff_over(node.event().pid, node.start_time(), stop)
+    }
+
+    /// the lifetimes of every object with their snapshots, in order of time.
+    pub fn object_histories(&self) -> Vec<ObjectHistory<'_>> {
+        let mut histories: Vec<_> = self
+            .object_life_cycle
+            .iter()
+            .flat_map(|(id, cct)| ObjectHistory::from_cct(id, cct))
+            .collect();
+        histories.sort_by_key(|history| {
+            let first = history.snapshots.first().map(|snapshot| snapshot.timestamp);
+            (history.created.or(first), history.id)
+        });
+        histories
+    }
+
+    /// the types of object by how much their snapshots changed, the most first.
+    pub fn snapshot_churn(&self) -> Vec<TypeChurn> {
+        churn_by_type(&self.object_histories())
+    }
+
+    /// every thread that is known, either by its events or by its metadata.
+    pub fn threads(&self) -> Vec<SyncTaskId> {
+        let mut threads: Vec<_> = self
+            .sync_tasks
+            .keys()
+            .chain(self.metadata.threads.keys())
+            .copied()
+            .collect();
+        threads.sort();
+        threads.dedup();
+        threads
+    }
+
+    /// gives every sync CCT the names of its process and thread from the metadata.
+    pub fn name_sync_tasks(&mut self) {
+        for ((pid, tid), cct) in self.sync_tasks.iter_mut() {
+            let process = self.metadata.processes.get(pid);
+            let thread = self.metadata.threads.get(&(*pid, *tid));
+            cct.set_names(
+                process.and_then(|p| p.name.as_deref()),
+                thread.and_then(|t| t.name.as_deref()),
+            );
+        }
+    }
+
+    /// the sync tasks in the order they are meant to be shown, i.e., by the sort index of their
+    /// process and then of their thread, falling back to their pid and tid.
+    pub fn sorted_sync_tasks(&self) -> Vec<(&SyncTaskId, &CCT)> {
+        let mut tasks: Vec<_> = self.sync_tasks.iter().collect();
+        tasks.sort_by_key(|((pid, tid), _)| {
+            let process = self.metadata.processes.get(pid);
+            let thread = self.metadata.threads.get(&(*pid, *tid));
+            (
+                process.and_then(|p| p.sort_index).unwrap_or(*pid),
+                *pid,
+                thread.and_then(|t| t.sort_index).unwrap_or(*tid),
+                *tid,
+            )
+        });
+        tasks
+    }
+}
+
+/// Markers are the instant events that apply to more than the thread emitting them, i.e., the
+/// process and global scoped instants. rather than being nested in the CCT of their thread, they
+/// are kept apart to be laid over the timeline of every thread they apply to.
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct Markers {
+    pub global: Vec<Event>,
+    pub process: HashMap<ProcessId, Vec<Event>>,
+}
+
+impl Markers {
+    /// adds an instant event to the track of its scope.
+    pub fn push(&mut self, event: Event) {
+        match event.instant_scope {
+            InstantScope::Global => self.global.push(event),
+            InstantScope::Process | InstantScope::Thread => {
+                self.process.entry(event.pid).or_default().push(event)
+            }
+        }
+    }
+
+    /// moves the markers of another Markers after the ones already in this one.
+    pub fn append(&mut self, mut other: Self) {
+        self.global.append(&mut other.global);
+        for (pid, mut events) in other.process.into_iter() {
+            self.process.entry(pid).or_default().append(&mut events)
+        }
+    }
+
+    /// moves the markers onto the clock of the reference.
+    pub fn rebase(&mut self, clock_sync: &ClockSync) {
+        let events = self
+            .global
+            .iter_mut()
+            .chain(self.process.values_mut().flatten());
+        for event in events {
+            clock_sync.rebase(event);
+        }
+    }
+
+    /// puts every track in order of time.
+    pub fn sort(&mut self) {
+        self.global.sort();
+        for events in self.process.values_mut() {
+            events.sort();
+        }
+    }
+
+    /// the markers that apply to the threads of a process from `start` up to `stop`, inclusive,
+    /// in order of time. e.g. the span of a CCT node gives the markers that fell within it.
+    pub fn within(&self, pid: ProcessId, start: Timestamp, stop: Timestamp) -> Vec<&Event> {
+        let global = span(&self.global, start, stop);
+        let process = span(
+            self.process.get(&pid).map_or(&[], Vec::as_slice),
+            start,
+            stop,
+        );
+
+        let mut markers: Vec<&Event> = global.iter().chain(process).collect();
+        markers.sort();
+        markers
+    }
+}
+
+/// the events of a sorted track from `start` up to `stop`, inclusive.
+fn span(events: &[Event], start: Timestamp, stop: Timestamp) -> &[Event] {
+    let from = events.partition_point(|e| e.timestamp < start);
+    let to = events.partition_point(|e| e.timestamp <= stop);
+    &events[from..to.max(from)]
 }
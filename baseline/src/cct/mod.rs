@@ -1,11 +1,11 @@
-use std::fmt::Display;
+use std::{collections::HashMap, fmt::Display};
 
 use log::{info, warn};
 
-use crate::{Event, EventPhase};
+use crate::{Event, EventPhase, Id, Timestamp};
 
 #[cfg(test)]
-mod verify;
+pub(crate) mod verify;
 
 mod visualize;
 
@@ -22,17 +22,25 @@ pub struct CCT {
 /// each node has an start and stop timestamp and holds the event from which the node
 /// is created.
 /// for event types that represent an instant in time, start and stop are equal.
+/// the thread start and stop timestamps are read from the thread's clock, if the tracer records
+/// it, and tell how long the node ran on a cpu.
+/// the contexts are the ids of the context objects, e.g. frames or isolates, that were entered on
+/// the thread when the node started, from the outermost.
 #[derive(Debug, Clone)]
 pub struct CCTNode {
     id: usize,
-    start_time: i64,
-    stop_time: Option<i64>,
+    start_time: Timestamp,
+    stop_time: Option<Timestamp>,
+    thread_start_time: Option<Timestamp>,
+    thread_stop_time: Option<Timestamp>,
     parent_node_id: Option<usize>,
+    contexts: Vec<Id>,
     event: Event,
 }
 
 /// CCTMeta holds metadata of the tree.
 /// currently only process name and thread name are supported.
+/// the names of a sync CCT are given by the metadata events of its process and thread.
 #[derive(Debug, Clone, Default)]
 pub struct CCTMeta {
     process_name: Option<String>,
@@ -42,28 +50,100 @@ pub struct CCTMeta {
 impl CCTNode {
     fn new(
         id: usize,
-        start_time: i64,
-        stop_time: Option<i64>,
+        start_time: Timestamp,
+        stop_time: Option<Timestamp>,
         parent_node_id: Option<usize>,
+        contexts: Vec<Id>,
         event: Event,
     ) -> Self {
         Self {
             id,
             start_time,
             stop_time,
+            thread_start_time: event.thread_timestamp,
+            thread_stop_time: None,
             parent_node_id,
+            contexts,
             event,
         }
     }
+
+    /// returns the event from which the node is created.
+    pub fn event(&self) -> &Event {
+        &self.event
+    }
+
+    /// returns the id of the node within its tree.
+    pub fn id(&self) -> usize {
+        self.id
+    }
+
+    /// returns the id of the node's parent, which is only missing for the root.
+    pub fn parent_id(&self) -> Option<usize> {
+        self.parent_node_id
+    }
+
+    pub fn start_time(&self) -> Timestamp {
+        self.start_time
+    }
+
+    pub fn stop_time(&self) -> Option<Timestamp> {
+        self.stop_time
+    }
+
+    /// returns the ids of the contexts the node ran within, from the outermost.
+    pub fn contexts(&self) -> &[Id] {
+        &self.contexts
+    }
+
+    /// denotes whether the node is a slice, i.e., it is made of a duration event rather than an
+    /// instant one.
+    pub fn is_slice(&self) -> bool {
+        matches!(
+            self.event.phase_type,
+            EventPhase::SyncBegin | EventPhase::Complete
+        )
+    }
+
+    /// denotes whether the node covers the given point in time.
+    pub fn contains(&self, time: Timestamp) -> bool {
+        self.start_time <= time && self.stop_time.is_none_or(|stop| time <= stop)
+    }
+
+    /// returns the wall time the node took.
+    pub fn wall_time(&self) -> Option<Timestamp> {
+        Some(self.stop_time? - self.start_time)
+    }
+
+    /// returns the time the node spent running on a cpu, as measured by its thread's clock.
+    pub fn cpu_time(&self) -> Option<Timestamp> {
+        Some(self.thread_stop_time? - self.thread_start_time?)
+    }
+
+    /// returns the time the node spent off the cpu, e.g. descheduled or waiting on a lock.
+    pub fn off_cpu_time(&self) -> Option<Timestamp> {
+        Some(self.wall_time()? - self.cpu_time()?)
+    }
 }
+impl CCTMeta {
+    pub fn process_name(&self) -> Option<&str> {
+        self.process_name.as_deref()
+    }
+
+    pub fn thread_name(&self) -> Option<&str> {
+        self.thread_name.as_deref()
+    }
+}
+
 impl CCT {
     /// creates a new CCT and allocates its first node as root.
     fn new() -> Self {
         let root = CCTNode::new(
             0,
-            i64::min_value(),
-            Some(i64::max_value()),
+            Timestamp::MIN,
+            Some(Timestamp::MAX),
             None,
+            Vec::new(),
             Default::default(),
         );
         Self {
@@ -72,6 +152,72 @@ impl CCT {
         }
     }
 
+    /// returns the metadata of the tree.
+    pub fn metadata(&self) -> &CCTMeta {
+        &self.metadata
+    }
+
+    /// names the process and the thread of the tree, keeping the names it already has if none
+    /// is given.
+    pub(crate) fn set_names(&mut self, process_name: Option<&str>, thread_name: Option<&str>) {
+        if let Some(name) = process_name {
+            self.metadata.process_name = Some(name.into());
+        }
+        if let Some(name) = thread_name {
+            self.metadata.thread_name = Some(name.into());
+        }
+    }
+
+    /// returns the node of the given id, if the tree has it.
+    pub fn node(&self, id: usize) -> Option<&CCTNode> {
+        self.nodes.get(id)
+    }
+
+    /// returns the innermost slice that covers the given point in time.
+    pub fn enclosing_slice(&self, time: Timestamp) -> Option<&CCTNode> {
+        // descendants are allocated after their ancestors
+        self.nodes
+            .iter()
+            .skip(1)
+            .rev()
+            .find(|node| node.is_slice() && node.contains(time))
+    }
+
+    /// returns the first slice that starts at or after the given point in time.
+    pub fn next_slice(&self, time: Timestamp) -> Option<&CCTNode> {
+        self.nodes
+            .iter()
+            .skip(1)
+            .filter(|node| node.is_slice() && node.start_time >= time)
+            .min_by_key(|node| node.start_time)
+    }
+
+    /// returns the nodes that ran within the given context.
+    pub fn nodes_in_context<'a>(&'a self, context: &'a Id) -> impl Iterator<Item = &'a CCTNode> {
+        self.nodes
+            .iter()
+            .skip(1)
+            .filter(move |node| node.contexts.contains(context))
+    }
+
+    /// returns the wall time spent within each context, where the time of a node is only counted
+    /// for the contexts its parent was not within already.
+    pub fn wall_time_by_context(&self) -> HashMap<Id, Timestamp> {
+        let mut times: HashMap<Id, Timestamp> = HashMap::new();
+        for node in self.nodes.iter().skip(1) {
+            let Some(wall_time) = node.wall_time() else {
+                continue;
+            };
+            let parent = node.parent_node_id.map(|id| self.get_node(id));
+            for context in &node.contexts {
+                if parent.is_none_or(|parent| !parent.contexts.contains(context)) {
+                    *times.entry(context.clone()).or_default() += wall_time;
+                }
+            }
+        }
+        times
+    }
+
     /// returns a refrence to the tree's root node.
     fn root(&self) -> &CCTNode {
         &self.nodes[0]
@@ -81,12 +227,20 @@ impl CCT {
     /// refrence to it.
     fn new_node(
         &mut self,
-        start_time: i64,
-        stop_time: Option<i64>,
+        start_time: Timestamp,
+        stop_time: Option<Timestamp>,
         parent: Option<usize>,
+        contexts: &[Id],
         event: Event,
     ) -> &CCTNode {
-        let node = CCTNode::new(self.nodes.len(), start_time, stop_time, parent, event);
+        let node = CCTNode::new(
+            self.nodes.len(),
+            start_time,
+            stop_time,
+            parent,
+            contexts.to_vec(),
+            event,
+        );
         self.nodes.push(node);
         self.nodes.last().unwrap()
     }
@@ -108,6 +262,8 @@ impl CCT {
         let mut cct = CCT::new();
         let mut stack = Vec::with_capacity(events.len() / 2);
         stack.push(cct.root().id);
+        // the contexts entered on the thread, from the outermost
+        let mut contexts: Vec<Id> = Vec::new();
 
         // some nodes are made from instant events or duration events which represent a full
         // node instead of half of a node. when poping the event stack to get a handle to the
@@ -123,7 +279,8 @@ impl CCT {
             // check for `EventPhase::Complete`s since this nodes have their stop time
             // available at construction
             while let Some(stop_time) = parent.stop_time {
-                if stop_time <= event.timestamp {
+                // the root spans every timestamp, even the latest one, and is never left
+                if stop_time <= event.timestamp && event_stack.len() > 1 {
                     event_stack.pop();
                     parent = cct.get_node(*event_stack.last().unwrap());
                 } else {
@@ -135,63 +292,86 @@ impl CCT {
 
         for mut event in events {
             match event.phase_type {
-                EventPhase::SyncBegin | EventPhase::AsyncBegin | EventPhase::ObjectCreate => {
+                EventPhase::SyncBegin
+                | EventPhase::AsyncBegin
+                | EventPhase::AsyncStart
+                | EventPhase::ObjectCreate => {
                     // create half of a node, set its parent, and push it into stack
                     let parent = pop_until_valid_parent(&cct, &mut stack, &event);
                     stack.push(
-                        cct.new_node(event.timestamp, None, Some(parent.id), event)
+                        cct.new_node(event.timestamp, None, Some(parent.id), &contexts, event)
                             .id,
                     );
                 }
 
-                EventPhase::SyncEnd | EventPhase::AsyncEnd | EventPhase::ObjectDestroy => {
-                    // pop a half node from stack and complete it.
-                    let id = loop {
-                        match stack.pop() {
-                            Some(id) => {
-                                if cct.get_node(id).stop_time.is_some() {
-                                    continue;
-                                }
-                                break id;
-                            }
-                            None => {
-                                warn!("found event {event:#?} with no matching start");
-                                continue;
-                            }
-                        }
+                EventPhase::SyncEnd
+                | EventPhase::AsyncEnd
+                | EventPhase::AsyncFinish
+                | EventPhase::ObjectDestroy => {
+                    // pop a half node from stack and complete it. an end with no half node to
+                    // complete is skipped and leaves the stack as it is.
+                    let open = stack
+                        .iter()
+                        .rposition(|id| cct.get_node(*id).stop_time.is_none());
+                    let Some(position) = open else {
+                        warn!("found event {event:#?} with no matching start");
+                        continue;
                     };
+                    let id = stack[position];
+                    stack.truncate(position);
 
                     let node = cct.get_node_mut(id);
                     node.stop_time = Some(event.timestamp);
+                    // the clock of a thread says nothing about async operations that might
+                    // end on another thread
+                    if event.phase_type == EventPhase::SyncEnd {
+                        node.thread_stop_time = event.thread_timestamp;
+                    }
                     node.event.merge(&mut event);
                 }
 
                 EventPhase::SyncInstant
                 | EventPhase::AsyncInstant
+                | EventPhase::AsyncStepInto
+                | EventPhase::AsyncStepPast
                 | EventPhase::ObjectSnapshot
                 | EventPhase::MemoryDumpProcess
                 | EventPhase::MemoryDumpGlobal
                 | EventPhase::Mark => {
                     // create a full node and set its parent
                     let parent = pop_until_valid_parent(&cct, &mut stack, &event);
-                    cct.new_node(
-                        event.timestamp,
-                        Some(event.timestamp),
-                        Some(parent.id),
-                        event,
-                    );
+                    let node_id = cct
+                        .new_node(
+                            event.timestamp,
+                            Some(event.timestamp),
+                            Some(parent.id),
+                            &contexts,
+                            event,
+                        )
+                        .id;
+                    let node = cct.get_node_mut(node_id);
+                    node.thread_stop_time = node.thread_start_time;
                 }
                 EventPhase::Complete => {
                     // create a full node and push into the stack
                     let parent = pop_until_valid_parent(&cct, &mut stack, &event);
+                    let thread_stop_time = event
+                        .thread_timestamp
+                        .zip(event.thread_duration)
+                        .map(|(tts, tdur)| tts.saturating_add(tdur));
                     let node_id = cct
                         .new_node(
                             event.timestamp,
-                            event.duration.or(Some(0)).map(|dur| dur + event.timestamp),
+                            event
+                                .duration
+                                .or(Some(0))
+                                .map(|dur| event.timestamp.saturating_add(dur)),
                             Some(parent.id),
+                            &contexts,
                             event,
                         )
                         .id;
+                    cct.get_node_mut(node_id).thread_stop_time = thread_stop_time;
                     stack.push(node_id);
                 }
                 EventPhase::Counter
@@ -199,9 +379,19 @@ impl CCT {
                 | EventPhase::Clock
                 | EventPhase::FlowStart
                 | EventPhase::FlowStep
-                | EventPhase::ContextEnter
-                | EventPhase::ContextLeave
-                | EventPhase::FlowEnd => ignored(&event),
+                | EventPhase::FlowEnd
+                | EventPhase::Unknown(_) => ignored(&event),
+                EventPhase::ContextEnter => contexts.push(event.id().clone()),
+                EventPhase::ContextLeave => {
+                    // contexts are left in the reverse order they are entered, though a context
+                    // left out of order is still taken off
+                    match contexts.iter().rposition(|id| id == event.id()) {
+                        Some(position) => {
+                            contexts.remove(position);
+                        }
+                        None => warn!("left context {} that was not entered", event.id()),
+                    }
+                }
                 EventPhase::Metadata => {
                     // update CCT metadata
                     let name = extract_name_from_args(&event);
@@ -233,7 +423,7 @@ impl CCT {
             .skip(1)
             .map(|node| node.stop_time)
             .max()
-            .unwrap_or(Some(i64::max_value()));
+            .unwrap_or(Some(Timestamp::MAX));
 
         self.nodes.iter_mut().for_each(|node| {
             if node.id == 0 {
@@ -257,17 +447,12 @@ impl From<Vec<Event>> for CCT {
 /// tries to extract name field from a json map
 /// returns a empty string if none is found
 fn extract_name_from_args(event: &Event) -> String {
-    if let Some(args) = &event.args {
-        if let serde_json::Value::Object(obj) = args {
-            let name = obj.get("name");
-            if let Some(name) = name {
-                if let serde_json::Value::String(name) = name {
-                    return String::from(name);
-                }
-            }
+    if let Some(serde_json::Value::Object(obj)) = &event.args {
+        if let Some(serde_json::Value::String(name)) = obj.get("name") {
+            return String::from(name);
         }
     }
-    return String::from("");
+    String::from("")
 }
 
 fn ignored(event: &Event) {
@@ -297,6 +482,18 @@ impl std::fmt::Display for CCT {
         for line in lines {
             writeln!(f, "{line}")?;
         }
+        for node in self.nodes.iter().skip(1) {
+            if let Some(cpu_time) = node.cpu_time() {
+                writeln!(
+                    f,
+                    "#{}# {}: wall {} cpu {cpu_time} off-cpu {}",
+                    node.id,
+                    node.event.name,
+                    node.wall_time().unwrap_or_default(),
+                    node.off_cpu_time().unwrap_or_default()
+                )?;
+            }
+        }
         Ok(())
     }
 }
@@ -327,10 +524,7 @@ impl Display for CCTNode {
             "{:*<45} <{}>::{}",
             format!(
                 "[{}] -> [{}]: ({},{}) ",
-                match self.parent_node_id {
-                    Some(id) => id,
-                    None => 0,
-                },
+                self.parent_node_id.unwrap_or_default(),
                 self.id,
                 self.start_time,
                 self.stop_time.unwrap_or(-1)
@@ -347,7 +541,10 @@ mod test {
 
     use rayon::iter::{IntoParallelRefIterator, ParallelIterator};
 
-    use crate::{build_application_cct, cct::verify, collect_traces, Trace};
+    use crate::{
+        build_application_cct, cct::verify, collect_traces, collect_traces_from_slice, Event,
+        EventPhase, Id, IdValue, Trace, CCT,
+    };
 
     /// ensures that the tree constraint holds, i.e.,
     /// for each pair of nodes (N1,N2) | N1 is an ancestor of N2 <==> N1 period encapsulates N2
@@ -359,15 +556,198 @@ mod test {
         app_cct
             .sync_tasks
             .par_iter()
-            .for_each(|(_, cct)| verify::assert_cct_valid(&cct));
+            .for_each(|(_, cct)| verify::assert_cct_valid(cct));
         app_cct
             .async_tasks
             .par_iter()
-            .for_each(|(_, cct)| verify::assert_cct_valid(&cct));
+            .for_each(|(_, cct)| verify::assert_cct_valid(cct));
         app_cct
             .object_life_cycle
             .par_iter()
-            .for_each(|(_, cct)| verify::assert_cct_valid(&cct));
+            .for_each(|(_, cct)| verify::assert_cct_valid(cct));
         Ok(())
     }
+
+    #[test]
+    fn check_fractional_timestamps_keep_their_precision() {
+        let events: Vec<Event> = serde_json::from_str(
+            r#"[
+            {"name":"a","cat":"c","ph":"X","pid":1,"tid":1,"ts":10.5,"dur":2.25},
+            {"name":"b","cat":"c","ph":"B","pid":1,"tid":1,"ts":10.75},
+            {"name":"b","cat":"c","ph":"E","pid":1,"tid":1,"ts":"11.001"},
+            {"name":"c","cat":"c","ph":"i","pid":1,"tid":1,"ts":12.7505}
+            ]"#,
+        )
+        .unwrap();
+        let mut cct = CCT::from(events);
+        verify::assert_cct_valid(&cct);
+
+        let times: Vec<_> = cct
+            .nodes
+            .iter()
+            .skip(1)
+            .map(|node| (node.start_time, node.stop_time))
+            .collect();
+        assert_eq!(
+            times,
+            [
+                (10_500, Some(12_750)),
+                (10_750, Some(11_001)),
+                (12_751, Some(12_751))
+            ]
+        );
+        assert_eq!(cct.nodes[3].parent_node_id, Some(0));
+
+        cct.normalize();
+        assert_eq!(cct.nodes[2].start_time, 250);
+    }
+
+    #[test]
+    fn check_legacy_async_phases_and_unknown_phases() -> std::io::Result<()> {
+        let trace = collect_traces_from_slice(
+            br#"[
+            {"name":"load","cat":"net","ph":"S","pid":1,"tid":1,"ts":10,"id":7},
+            {"name":"load","cat":"net","ph":"T","pid":1,"tid":1,"ts":11,"id":7,"args":{"step":"dns"}},
+            {"name":"load","cat":"net","ph":"p","pid":1,"tid":2,"ts":12,"id":7,"args":{"step":"tcp"}},
+            {"name":"future","cat":"net","ph":"z","pid":1,"tid":1,"ts":13},
+            {"name":"load","cat":"net","ph":"F","pid":1,"tid":2,"ts":14,"id":7}
+            ]"#,
+        )?;
+        assert_eq!(trace.events[3].phase_type, EventPhase::Unknown("z".into()));
+        assert_eq!(trace.events[3].phase_type.to_string(), "z");
+
+        let app_cct = build_application_cct(trace);
+        assert_eq!(app_cct.async_tasks.len(), 1);
+        let cct = app_cct.async_tasks.values().next().unwrap();
+        verify::assert_cct_valid(cct);
+
+        let nodes: Vec<_> = cct
+            .nodes
+            .iter()
+            .skip(1)
+            .map(|node| (node.start_time, node.stop_time, node.parent_node_id))
+            .collect();
+        assert_eq!(
+            nodes,
+            [
+                (10_000, Some(14_000), Some(0)),
+                (11_000, Some(11_000), Some(1)),
+                (12_000, Some(12_000), Some(1))
+            ]
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn check_unmatched_ends_are_skipped() {
+        let events: Vec<Event> = serde_json::from_str(
+            r#"[
+            {"name":"a","cat":"c","ph":"E","pid":1,"tid":1,"ts":1},
+            {"name":"a","cat":"c","ph":"E","pid":1,"tid":1,"ts":2},
+            {"name":"b","cat":"c","ph":"X","pid":1,"tid":1,"ts":3,"dur":4},
+            {"name":"b","cat":"c","ph":"e","pid":1,"tid":1,"ts":4,"id":1},
+            {"name":"c","cat":"c","ph":"B","pid":1,"tid":1,"ts":5},
+            {"name":"c","cat":"c","ph":"E","pid":1,"tid":1,"ts":6},
+            {"name":"c","cat":"c","ph":"F","pid":1,"tid":1,"ts":8,"id":1}
+            ]"#,
+        )
+        .unwrap();
+        let cct = CCT::from(events);
+        verify::assert_cct_valid(&cct);
+
+        let nodes: Vec<_> = cct
+            .nodes
+            .iter()
+            .skip(1)
+            .map(|node| (node.start_time, node.stop_time, node.parent_node_id))
+            .collect();
+        assert_eq!(
+            nodes,
+            [(3_000, Some(7_000), Some(0)), (5_000, Some(6_000), Some(1))]
+        );
+
+        let trace = collect_traces_from_slice(
+            br#"[
+            {"name":"a","cat":"c","ph":"E","pid":1,"tid":1,"ts":1},
+            {"name":"a","cat":"c","ph":"E","pid":1,"tid":1,"ts":2}
+            ]"#,
+        )
+        .unwrap();
+        let app_cct = build_application_cct(trace);
+        assert_eq!(app_cct.sync_tasks[&(1, 1)].into_iter().count(), 1);
+    }
+
+    #[test]
+    fn check_thread_time_gives_cpu_and_off_cpu_time() {
+        let events: Vec<Event> = serde_json::from_str(
+            r#"[
+            {"name":"a","cat":"c","ph":"B","pid":1,"tid":1,"ts":10,"tts":100},
+            {"name":"b","cat":"c","ph":"X","pid":1,"tid":1,"ts":11,"dur":4,"tts":101,"tdur":1.5},
+            {"name":"c","cat":"c","ph":"X","pid":1,"tid":1,"ts":16,"dur":1},
+            {"name":"a","cat":"c","ph":"E","pid":1,"tid":1,"ts":20,"tts":104}
+            ]"#,
+        )
+        .unwrap();
+        let cct = CCT::from(events);
+        verify::assert_cct_valid(&cct);
+
+        let times: Vec<_> = cct
+            .into_iter()
+            .skip(1)
+            .map(|node| (node.wall_time(), node.cpu_time(), node.off_cpu_time()))
+            .collect();
+        assert_eq!(
+            times,
+            [
+                (Some(10_000), Some(4_000), Some(6_000)),
+                (Some(4_000), Some(1_500), Some(2_500)),
+                (Some(1_000), None, None)
+            ]
+        );
+        assert!(format!("{cct}").contains("#1# a: wall 10000 cpu 4000 off-cpu 6000"));
+        assert!(format!("{cct}").contains("#2# b: wall 4000 cpu 1500 off-cpu 2500"));
+        // the timing is rendered for people only, the debug output stays structural
+        assert!(!format!("{cct:?}").contains("cpu"));
+    }
+
+    #[test]
+    fn check_context_events_annotate_nodes() {
+        let events: Vec<Event> = serde_json::from_str(
+            r#"[
+            {"name":"FrameBlameContext","cat":"blink","ph":"(","pid":1,"tid":1,"ts":10,"id":"0x1"},
+            {"name":"a","cat":"c","ph":"X","pid":1,"tid":1,"ts":10,"dur":10},
+            {"name":"Isolate","cat":"v8","ph":"(","pid":1,"tid":1,"ts":12,"id2":{"local":"0x7"}},
+            {"name":"b","cat":"c","ph":"X","pid":1,"tid":1,"ts":12,"dur":4},
+            {"name":"Isolate","cat":"v8","ph":")","pid":1,"tid":1,"ts":16,"id2":{"local":"0x7"}},
+            {"name":"FrameBlameContext","cat":"blink","ph":")","pid":1,"tid":1,"ts":20,"id":"0x1"},
+            {"name":"c","cat":"c","ph":"X","pid":1,"tid":1,"ts":21,"dur":1},
+            {"name":"FrameBlameContext","cat":"blink","ph":")","pid":1,"tid":1,"ts":22,"id":"0x1"}
+            ]"#,
+        )
+        .unwrap();
+        let frame = Id::Global(IdValue::Int(1));
+        let isolate = Id::Local(IdValue::Int(7));
+        let cct = CCT::from(events);
+        verify::assert_cct_valid(&cct);
+
+        let contexts: Vec<_> = cct
+            .into_iter()
+            .skip(1)
+            .map(|node| (node.event().name.as_str(), node.contexts()))
+            .collect();
+        assert_eq!(
+            contexts,
+            [
+                ("a", &[frame.clone()][..]),
+                ("b", &[frame.clone(), isolate.clone()][..]),
+                ("c", &[][..]),
+            ]
+        );
+        assert_eq!(cct.nodes_in_context(&frame).count(), 2);
+        assert_eq!(cct.nodes_in_context(&isolate).count(), 1);
+
+        let times = cct.wall_time_by_context();
+        assert_eq!(times[&frame], 10_000);
+        assert_eq!(times[&isolate], 4_000);
+    }
 }
@@ -1,4 +1,5 @@
 use super::CCT;
+use crate::Timestamp;
 
 /// VisualTree holds the visual represntation of the CCT which is later used by CCT to generate
 /// Display and Debug functionality.
@@ -49,8 +50,8 @@ pub fn build_visual_tree(cct: &CCT) -> VisualTree {
 /// VisualNode is a visual representation of a CCT node.
 struct VisualNode {
     id: usize,
-    start: i64,
-    end: i64,
+    start: Timestamp,
+    end: Timestamp,
     children: Vec<usize>,
 }
 
@@ -59,7 +60,7 @@ pub fn visualize_tree(tree: &VisualTree, max_char: usize) -> Vec<String> {
     let root = &tree.nodes[0];
     let mut lines = Vec::new();
     lines.push(format!("|{:-^width$}|", "#0#", width = max_char - 2));
-    visualize(&root, tree, max_char, 0, &mut lines, 1);
+    visualize(root, tree, max_char, 0, &mut lines, 1);
     lines
 }
 
@@ -72,7 +73,7 @@ fn visualize(
     lines: &mut Vec<String>,
     h: usize,
 ) {
-    if root.children.len() == 0 {
+    if root.children.is_empty() {
         return;
     }
     if h >= lines.len() {
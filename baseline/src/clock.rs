@@ -0,0 +1,354 @@
+use std::collections::HashMap;
+
+use log::{info, warn};
+use serde::Deserialize;
+use serde_json::{json, Value};
+
+use crate::{
+    application::SyncTaskId, profile::sample_pid, Event, EventPhase, ProcessId, Timestamp,
+    TraceFields,
+};
+
+/// ClockOffset is how far the clock of a process is from the clock the trace is aligned on,
+/// i.e., what is added to its timestamps, give or take the uncertainty.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ClockOffset {
+    pub offset: Timestamp,
+    pub uncertainty: Timestamp,
+    /// the clock sync that the offset is estimated from.
+    pub sync_id: String,
+    /// the process the sync is with, which is the reference unless the clock is aligned through
+    /// another one.
+    pub synced_with: ProcessId,
+}
+
+impl std::fmt::Display for ClockOffset {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{}ns ± {}ns (sync {})",
+            self.offset, self.uncertainty, self.sync_id
+        )
+    }
+}
+
+/// ClockSync aligns the clocks of the processes of a trace, e.g. of the browser, the gpu process
+/// and the system, by the clock sync events, i.e., the ones of phase `c`.
+/// a clock sync is issued from one clock, whose event tells when it was issued in `issue_ts` and
+/// when it returned in `ts`, and is received by another clock, whose event tells when in `ts`.
+/// the sync is taken to be received halfway through, so the uncertainty is half of the round
+/// trip.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ClockSync {
+    /// the process whose clock the trace is aligned on, which is the first to issue a sync.
+    pub reference: Option<ProcessId>,
+    /// the offsets of the processes synced with the reference, either directly or through other
+    /// processes. other processes are left as they are.
+    pub offsets: HashMap<ProcessId, ClockOffset>,
+}
+
+/// the clock sync events of a sync, as the pid and the times of each of them.
+#[derive(Default)]
+struct Sync {
+    issued: Vec<(ProcessId, Timestamp, Timestamp)>,
+    received: Vec<(ProcessId, Timestamp)>,
+}
+
+impl ClockSync {
+    /// estimates the offsets of the clocks from the clock sync events among `events`, keeping
+    /// the path of syncs of least uncertainty from the reference to each process.
+    pub fn from_events<'a>(events: impl IntoIterator<Item = &'a Event>) -> Self {
+        let mut syncs: HashMap<String, Sync> = HashMap::new();
+        for event in events {
+            if event.phase_type != EventPhase::Clock {
+                continue;
+            }
+            let arg = |key| event.args.as_ref().and_then(|args| args.get(key));
+            let sync_id = match arg("sync_id") {
+                Some(Value::String(id)) => id.clone(),
+                Some(Value::Number(id)) => id.to_string(),
+                _ => {
+                    warn!("clock sync at {} has no sync id", event.timestamp);
+                    continue;
+                }
+            };
+            let sync = syncs.entry(sync_id).or_default();
+            match arg("issue_ts").and_then(Value::as_f64) {
+                Some(issued) if !utils::fits_nanos(issued) => {
+                    warn!("clock sync at {} is issued out of range", event.timestamp);
+                }
+                Some(issued) => {
+                    let issued = utils::micros_to_nanos(issued);
+                    sync.issued.push((event.pid, issued, event.timestamp))
+                }
+                None => sync.received.push((event.pid, event.timestamp)),
+            }
+        }
+
+        let reference = syncs
+            .values()
+            .flat_map(|sync| &sync.issued)
+            .min_by_key(|(pid, _, returned)| (*returned, *pid))
+            .map(|(pid, _, _)| *pid);
+        let Some(reference) = reference else {
+            return Default::default();
+        };
+
+        // the sync of least uncertainty between each pair of clocks, as the offset that moves
+        // the times of the first onto the clock of the second
+        let mut links: HashMap<(ProcessId, ProcessId), ClockOffset> = HashMap::new();
+        for (sync_id, sync) in &syncs {
+            for (issuer, issued, returned) in &sync.issued {
+                // the midpoint of two timestamps always fits, even if their sum does not
+                let midpoint = ((*issued as i128 + *returned as i128) / 2) as Timestamp;
+                let Some(uncertainty) = returned.checked_sub(*issued).map(|trip| trip / 2) else {
+                    warn!("clock sync {sync_id} takes too long to be of use");
+                    continue;
+                };
+                for (receiver, received) in sync.received.iter().filter(|(pid, _)| pid != issuer) {
+                    let (Some(backward), Some(forward)) = (
+                        midpoint.checked_sub(*received),
+                        received.checked_sub(midpoint),
+                    ) else {
+                        warn!("clock sync {sync_id} is too far off to be of use");
+                        continue;
+                    };
+                    for (from, to, offset) in [
+                        (*receiver, *issuer, backward),
+                        (*issuer, *receiver, forward),
+                    ] {
+                        let link = ClockOffset {
+                            offset,
+                            uncertainty,
+                            sync_id: sync_id.clone(),
+                            synced_with: to,
+                        };
+                        let best = links.entry((from, to)).or_insert(link.clone());
+                        if (uncertainty, sync_id) < (best.uncertainty, &best.sync_id) {
+                            *best = link;
+                        }
+                    }
+                }
+            }
+        }
+
+        // the clocks that are not synced with the reference directly are moved through the ones
+        // they are synced with, along the path of least uncertainty, which adds up the offsets
+        // and the uncertainties of the syncs on it.
+        let mut offsets: HashMap<ProcessId, ClockOffset> = HashMap::new();
+        loop {
+            let aligned = |pid: &ProcessId| *pid == reference || offsets.contains_key(pid);
+            let next = links
+                .iter()
+                .filter(|((from, to), _)| !aligned(from) && aligned(to))
+                // a path whose offsets add up beyond what a timestamp holds is of no use
+                .filter_map(|((from, to), link)| {
+                    let base = offsets.get(to).cloned().unwrap_or_default();
+                    let offset = ClockOffset {
+                        offset: base.offset.checked_add(link.offset)?,
+                        uncertainty: base.uncertainty.checked_add(link.uncertainty)?,
+                        ..link.clone()
+                    };
+                    Some((*from, offset))
+                })
+                .min_by_key(|(pid, offset)| {
+                    let ClockOffset {
+                        uncertainty,
+                        sync_id,
+                        synced_with,
+                        ..
+                    } = offset;
+                    (*uncertainty, *pid, *synced_with, sync_id.clone())
+                });
+            let Some((pid, offset)) = next else {
+                break;
+            };
+            info!("clock of process {pid} is off by {offset} from process {reference}");
+            offsets.insert(pid, offset);
+        }
+        let mut unreachable: Vec<_> = links
+            .keys()
+            .map(|(pid, _)| *pid)
+            .filter(|pid| *pid != reference && !offsets.contains_key(pid))
+            .collect();
+        unreachable.sort();
+        unreachable.dedup();
+        for pid in unreachable {
+            warn!("clock of process {pid} is not synced with process {reference} and is left");
+        }
+
+        ClockSync {
+            reference: Some(reference),
+            offsets,
+        }
+    }
+
+    /// denotes whether no clock is to be moved.
+    pub fn is_empty(&self) -> bool {
+        self.offsets.is_empty()
+    }
+
+    /// what is added to the timestamps of a process.
+    pub fn offset(&self, pid: ProcessId) -> Timestamp {
+        self.offsets.get(&pid).map_or(0, |offset| offset.offset)
+    }
+
+    /// moves a timestamp of a process onto the clock of the reference. a timestamp that would
+    /// be moved beyond what a timestamp holds is saturated.
+    pub fn rebase_timestamp(&self, pid: ProcessId, timestamp: Timestamp) -> Timestamp {
+        timestamp.saturating_add(self.offset(pid))
+    }
+
+    /// moves the timestamp of an event onto the clock of the reference.
+    /// the thread clock of the event is its own and is left as it is.
+    pub fn rebase(&self, event: &mut Event) {
+        event.timestamp = self.rebase_timestamp(event.pid, event.timestamp);
+    }
+
+    /// moves the samples of the `samples` table of a trace onto the clock of the reference. the
+    /// process of a sample that only tells its thread is the one of the thread among `threads`.
+    pub fn rebase_samples(&self, fields: &mut TraceFields, threads: &[SyncTaskId]) {
+        let Some(Value::Array(samples)) = &mut fields.samples else {
+            return;
+        };
+        for sample in samples.iter_mut() {
+            let Ok(event) = Event::deserialize(&*sample) else {
+                continue;
+            };
+            let pid = sample_pid(sample, &event, threads);
+            if self.offset(pid) == 0 {
+                continue;
+            }
+            let timestamp = self.rebase_timestamp(pid, event.timestamp);
+            sample["ts"] = match timestamp % utils::NANOS_PER_MICRO {
+                0 => json!(timestamp / utils::NANOS_PER_MICRO),
+                _ => json!(timestamp as f64 / utils::NANOS_PER_MICRO as f64),
+            };
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::path::Path;
+
+    use crate::{build_application_cct, collect_traces, collect_traces_from_slice};
+
+    #[test]
+    fn check_clocks_are_aligned_on_the_issuer() -> std::io::Result<()> {
+        let trace = collect_traces(Path::new("../data/trace-clock.json"))?;
+        let app_cct = build_application_cct(trace);
+
+        let clock_sync = &app_cct.clock_sync;
+        assert_eq!(clock_sync.reference, Some(1));
+        assert_eq!(clock_sync.offsets.len(), 1);
+        // of the two syncs of the gpu process, the one of the shorter round trip is kept
+        let gpu = &clock_sync.offsets[&2];
+        assert_eq!((gpu.offset, gpu.uncertainty), (-4_900_000, 5_000));
+        assert_eq!(gpu.sync_id, "abc");
+        assert_eq!(clock_sync.offset(3), 0);
+
+        let start = |task, name: &str| {
+            let cct = &app_cct.sync_tasks[&task];
+            let node = cct.into_iter().find(|node| node.event().name == name);
+            node.unwrap().start_time()
+        };
+        assert_eq!(start((1, 1), "Submit"), 200_000);
+        assert_eq!(start((2, 1), "Draw"), 205_000);
+        assert_eq!(start((3, 1), "Idle"), 400_000);
+        assert_eq!(app_cct.fields.samples.as_ref().unwrap()[0]["ts"], 210);
+
+        // the async task spans both processes, which are only in order once aligned
+        let (_, frame) = app_cct.async_tasks.iter().next().unwrap();
+        let frame = frame.node(1).unwrap();
+        assert_eq!(
+            (frame.start_time(), frame.stop_time()),
+            (150_000, Some(220_000))
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn check_clocks_are_aligned_through_other_clocks() -> std::io::Result<()> {
+        let trace = collect_traces_from_slice(
+            br#"{"traceEvents":[
+            {"name":"clock_sync","ph":"c","pid":1,"tid":1,"ts":110,"args":{"sync_id":"a","issue_ts":100}},
+            {"name":"clock_sync","ph":"c","pid":2,"tid":1,"ts":1105,"args":{"sync_id":"a"}},
+            {"name":"clock_sync","ph":"c","pid":2,"tid":1,"ts":2010,"args":{"sync_id":"b","issue_ts":2000}},
+            {"name":"clock_sync","ph":"c","pid":3,"tid":1,"ts":5005,"args":{"sync_id":"b"}},
+            {"name":"clock_sync","ph":"c","pid":4,"tid":1,"ts":3000,"args":{"sync_id":"c","issue_ts":2990}},
+            {"name":"clock_sync","ph":"c","pid":5,"tid":1,"ts":9000,"args":{"sync_id":"c"}},
+            {"name":"Work","cat":"c","ph":"X","pid":3,"tid":7,"ts":5100,"dur":10},
+            {"name":"Frame","cat":"c","ph":"b","pid":2,"tid":1,"ts":1200,"id":5},
+            {"name":"Frame","cat":"c","ph":"e","pid":3,"tid":7,"ts":4100,"id":5}
+            ],
+            "samples":[
+            {"name":"cycles","tid":7,"ts":5200,"sf":1},
+            {"name":"cycles","pid":2,"tid":1,"ts":1300.5,"sf":1}
+            ]}"#,
+        )?;
+        let app_cct = build_application_cct(trace);
+
+        let clock_sync = &app_cct.clock_sync;
+        assert_eq!(clock_sync.reference, Some(1));
+        let offsets = |pid| {
+            let offset = &clock_sync.offsets[&pid];
+            (
+                offset.offset,
+                offset.uncertainty,
+                offset.sync_id.as_str(),
+                offset.synced_with,
+            )
+        };
+        assert_eq!(offsets(2), (-1_000_000, 5_000, "a", 1));
+        // the offsets and the uncertainties add up along the chain of syncs
+        assert_eq!(offsets(3), (-4_000_000, 10_000, "b", 2));
+        // the clocks that are only synced with each other are left as they are
+        assert_eq!(clock_sync.offsets.len(), 2);
+        assert_eq!((clock_sync.offset(4), clock_sync.offset(5)), (0, 0));
+
+        let work = app_cct.sync_tasks[&(3, 7)].node(1).unwrap();
+        assert_eq!(work.start_time(), 1_100_000);
+
+        // the samples of the table are moved along with the events of their process
+        let samples = app_cct.fields.samples.as_ref().unwrap();
+        assert_eq!(samples[0]["ts"], 1200);
+        assert_eq!(samples[1]["ts"], 300.5);
+
+        // once aligned, the end of the frame comes before its begin and is skipped
+        let frame = app_cct.async_tasks.values().next().unwrap();
+        let frame = frame.node(1).unwrap();
+        assert_eq!((frame.start_time(), frame.stop_time()), (200_000, None));
+        Ok(())
+    }
+
+    #[test]
+    fn check_clock_syncs_out_of_range_are_left_out() -> std::io::Result<()> {
+        let trace = collect_traces_from_slice(
+            br#"[
+            {"name":"clock_sync","ph":"c","pid":1,"tid":1,"ts":10,"args":{"sync_id":"a","issue_ts":1e300}},
+            {"name":"clock_sync","ph":"c","pid":2,"tid":1,"ts":20,"args":{"sync_id":"a"}},
+            {"name":"clock_sync","ph":"c","pid":1,"tid":1,"ts":0,"args":{"sync_id":"b","issue_ts":0}},
+            {"name":"clock_sync","ph":"c","pid":3,"tid":1,"ts":-9000000000000000,"args":{"sync_id":"b"}},
+            {"name":"clock_sync","ph":"c","pid":1,"tid":1,"ts":9000000000000000,"args":{"sync_id":"c","issue_ts":9000000000000000}},
+            {"name":"clock_sync","ph":"c","pid":4,"tid":1,"ts":-9000000000000000,"args":{"sync_id":"c"}},
+            {"name":"Work","cat":"c","ph":"X","pid":3,"tid":7,"ts":9000000000000000,"dur":10}
+            ]"#,
+        )?;
+        let app_cct = build_application_cct(trace);
+
+        // the sync issued out of range is dropped, and so is the one whose offset overflows
+        let clock_sync = &app_cct.clock_sync;
+        assert_eq!(clock_sync.reference, Some(1));
+        assert_eq!(clock_sync.offsets.len(), 1);
+        assert_eq!(clock_sync.offset(3), 9_000_000_000_000_000_000);
+
+        // the timestamps moved beyond the range are saturated
+        let work = app_cct.sync_tasks[&(3, 7)].node(1).unwrap();
+        assert_eq!(
+            (work.start_time(), work.stop_time()),
+            (i64::MAX, Some(i64::MAX))
+        );
+        Ok(())
+    }
+}
@@ -0,0 +1,252 @@
+use std::collections::HashMap;
+
+use log::debug;
+use serde_json::Value;
+
+use crate::{CCTNode, ClockSync, Event, Id, ProcessId, Timestamp};
+
+/// counters are told apart by their process, name and id.
+pub type CounterId = (ProcessId, String, Id);
+
+/// Series is the values of a single key of a counter over time. a counter keeps its value until
+/// the next one is recorded, so the value at a given time is the last one recorded up to it.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Series {
+    points: Vec<(Timestamp, f64)>,
+}
+
+impl Series {
+    /// every value of the series along with the time it was recorded, in order of time.
+    pub fn points(&self) -> &[(Timestamp, f64)] {
+        &self.points
+    }
+
+    /// the value of the counter at `time`, if any was recorded up to it.
+    pub fn at(&self, time: Timestamp) -> Option<f64> {
+        let index = self.points.partition_point(|(ts, _)| *ts <= time);
+        index.checked_sub(1).map(|i| self.points[i].1)
+    }
+
+    /// the values recorded from `start` up to `stop`, inclusive.
+    pub fn range(&self, start: Timestamp, stop: Timestamp) -> &[(Timestamp, f64)] {
+        let from = self.points.partition_point(|(ts, _)| *ts < start);
+        let to = self.points.partition_point(|(ts, _)| *ts <= stop);
+        &self.points[from..to.max(from)]
+    }
+
+    /// the lowest value the counter takes from `start` up to `stop`.
+    pub fn min(&self, start: Timestamp, stop: Timestamp) -> Option<f64> {
+        self.window(start, stop).reduce(f64::min)
+    }
+
+    /// the highest value the counter takes from `start` up to `stop`.
+    pub fn max(&self, start: Timestamp, stop: Timestamp) -> Option<f64> {
+        self.window(start, stop).reduce(f64::max)
+    }
+
+    /// the average value of the counter from `start` up to `stop`, where each value is weighted by
+    /// the time it is held for. the window starts with the first value if none is recorded
+    /// before `start`, and an empty window gives the value at its time.
+    pub fn avg(&self, start: Timestamp, stop: Timestamp) -> Option<f64> {
+        let first = self.points.first()?.0;
+        let start = start.max(first);
+        if stop < start {
+            return None;
+        }
+        if stop == start {
+            return self.at(start);
+        }
+
+        let mut sum = 0.0;
+        let mut time = start;
+        let mut value = self.at(start)?;
+        for (ts, next) in self.range(start + 1, stop) {
+            sum += value * (ts - time) as f64;
+            (time, value) = (*ts, *next);
+        }
+        sum += value * (stop - time) as f64;
+        Some(sum / (stop - start) as f64)
+    }
+
+    /// the values the counter takes from `start` up to `stop`, i.e., the one held at `start` and
+    /// the ones recorded after it.
+    fn window(&self, start: Timestamp, stop: Timestamp) -> impl Iterator<Item = f64> + '_ {
+        let held = if stop < start { None } else { self.at(start) };
+        let recorded = self.range(start.saturating_add(1), stop);
+        held.into_iter()
+            .chain(recorded.iter().map(|(_, value)| *value))
+    }
+}
+
+/// Counter is a counter event track, e.g. the heap size and the number of nodes of
+/// `UpdateCounters`, where each key of the `args` of the events is a series of its own.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Counter {
+    pub series: HashMap<String, Series>,
+}
+
+impl Counter {
+    /// the series of a key of the counter.
+    pub fn get(&self, key: &str) -> Option<&Series> {
+        self.series.get(key)
+    }
+}
+
+/// CounterValues is what a series of a counter reads at the start and the stop of a CCT node.
+#[derive(Debug, Clone, PartialEq)]
+pub struct CounterValues<'a> {
+    pub counter: &'a CounterId,
+    pub key: &'a str,
+    pub start: Option<f64>,
+    pub stop: Option<f64>,
+}
+
+/// Counters gathers the counter events of a trace, i.e., the ones of phase `C`, as time series.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Counters {
+    pub counters: HashMap<CounterId, Counter>,
+}
+
+impl Counters {
+    /// records the values of a counter event. values that are not numbers are left out.
+    pub fn push(&mut self, event: Event) {
+        let id = event.id().clone();
+        let Some(Value::Object(args)) = event.args else {
+            debug!("counter {} has no values", event.name);
+            return;
+        };
+        let id = (event.pid, event.name, id);
+        let counter = self.counters.entry(id).or_default();
+        for (key, value) in args {
+            let value = match value {
+                Value::Number(n) => n.as_f64(),
+                Value::String(s) => s.trim().parse().ok(),
+                _ => None,
+            };
+            if let Some(value) = value {
+                let series = counter.series.entry(key).or_default();
+                series.points.push((event.timestamp, value));
+            }
+        }
+    }
+
+    /// moves the values of another Counters after the ones already in this one.
+    pub fn append(&mut self, other: Self) {
+        for (id, other) in other.counters {
+            let counter = self.counters.entry(id).or_default();
+            for (key, mut series) in other.series {
+                let points = &mut counter.series.entry(key).or_default().points;
+                points.append(&mut series.points);
+            }
+        }
+    }
+
+    /// moves the values onto the clock of the reference.
+    pub fn rebase(&mut self, clock_sync: &ClockSync) {
+        for ((pid, _, _), counter) in self.counters.iter_mut() {
+            for series in counter.series.values_mut() {
+                for (ts, _) in series.points.iter_mut() {
+                    *ts = clock_sync.rebase_timestamp(*pid, *ts);
+                }
+            }
+        }
+    }
+
+    /// puts every series in order of time, keeping the order of the values at the same time.
+    pub fn sort(&mut self) {
+        for counter in self.counters.values_mut() {
+            for series in counter.series.values_mut() {
+                series.points.sort_by_key(|(ts, _)| *ts);
+            }
+        }
+    }
+
+    /// the counter of a process with the given name and id.
+    pub fn get(&self, pid: ProcessId, name: &str, id: &Id) -> Option<&Counter> {
+        self.counters.get(&(pid, name.to_string(), id.clone()))
+    }
+
+    /// what every series of the counters of a process reads at the start and the stop of a node,
+    /// ordered by counter and key.
+    pub fn at_node(&self, pid: ProcessId, node: &CCTNode) -> Vec<CounterValues<'_>> {
+        let mut values: Vec<_> = self
+            .counters
+            .iter()
+            .filter(|((counter_pid, _, _), _)| *counter_pid == pid)
+            .flat_map(|(id, counter)| {
+                counter
+                    .series
+                    .iter()
+                    .map(move |(key, series)| CounterValues {
+                        counter: id,
+                        key,
+                        start: series.at(node.start_time()),
+                        stop: node.stop_time().and_then(|stop| series.at(stop)),
+                    })
+            })
+            .collect();
+        values.sort_by(|a, b| (a.counter, a.key).cmp(&(b.counter, b.key)));
+        values
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::path::Path;
+
+    use crate::{build_application_cct, collect_traces, Id, IdValue};
+
+    #[test]
+    fn check_counters_are_split_into_series() -> std::io::Result<()> {
+        let trace = collect_traces(Path::new("../data/trace-counters.json"))?;
+        let app_cct = build_application_cct(trace);
+
+        let memory = app_cct.counters.get(1, "Memory", &Id::None).unwrap();
+        let heap = memory.get("heap").unwrap();
+        assert_eq!(heap.points(), [(0, 10.0), (10000, 30.0), (20000, 20.0)]);
+        assert_eq!(memory.get("nodes").unwrap().at(9999), Some(3.0));
+        assert_eq!(memory.get("label"), None);
+        assert_eq!(
+            app_cct
+                .counters
+                .get(2, "Memory", &Id::None)
+                .unwrap()
+                .series
+                .len(),
+            1
+        );
+
+        assert_eq!(heap.at(-1), None);
+        assert_eq!(heap.at(15000), Some(30.0));
+        assert_eq!(heap.range(0, 10000).len(), 2);
+        assert_eq!(heap.min(5000, 25000), Some(10.0));
+        assert_eq!(heap.max(5000, 25000), Some(30.0));
+        assert_eq!(heap.min(11000, 25000), Some(20.0));
+        assert_eq!(heap.avg(5000, 15000), Some(20.0));
+        assert_eq!(heap.avg(10000, 30000), Some(25.0));
+        assert_eq!(heap.avg(30000, 30000), Some(20.0));
+
+        let layout = app_cct.sync_tasks[&(1, 1)].node(1).unwrap();
+        let values: Vec<_> = app_cct
+            .counters_at(layout)
+            .into_iter()
+            .map(|values| {
+                (
+                    values.counter.2.clone(),
+                    values.key,
+                    values.start,
+                    values.stop,
+                )
+            })
+            .collect();
+        assert_eq!(
+            values,
+            [
+                (Id::None, "heap", Some(10.0), Some(30.0)),
+                (Id::None, "nodes", Some(3.0), Some(5.0)),
+                (Id::Global(IdValue::Int(1)), "queue", Some(4.0), Some(4.0)),
+            ]
+        );
+        Ok(())
+    }
+}
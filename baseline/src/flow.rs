@@ -0,0 +1,219 @@
+use std::collections::HashMap;
+
+use log::debug;
+
+use crate::{application::SyncTaskId, CCTNode, Category, Event, EventPhase, Id, ProcessId, CCT};
+
+/// NodeRef points to a node of a sync CCT, i.e., the CCT of a thread.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct NodeRef {
+    pub task: SyncTaskId,
+    pub node: usize,
+}
+
+/// FlowEdge links the node where a flow, or a step of it, comes from to the node it goes to.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FlowEdge {
+    pub id: Id,
+    pub category: Category,
+    pub name: String,
+    pub from: NodeRef,
+    pub to: NodeRef,
+}
+
+/// FlowGraph holds the causality between the nodes of the sync CCTs, e.g. a task posted on one
+/// thread and run on another, as edges that can be followed in both directions.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct FlowGraph {
+    edges: Vec<FlowEdge>,
+    outgoing: HashMap<NodeRef, Vec<usize>>,
+    incoming: HashMap<NodeRef, Vec<usize>>,
+}
+
+/// flows are told apart by their category and id, where local ids are qualified by the pid of
+/// their process.
+type FlowId = (Category, Option<ProcessId>, Id);
+
+impl FlowGraph {
+    /// resolves flows into edges between the nodes of the sync CCTs.
+    /// `events` are the flow events, i.e., of phases `s`, `t` and `f`, where a flow goes from its
+    /// start through its steps up to its end, after which its id may be reused. a flow start or
+    /// step binds to the slice enclosing it on its thread, and so does a flow end whose binding
+    /// point is `e`, while any other flow end binds to the next slice that starts on its thread.
+    /// slices may also be bound to flows by their `bind_id`, in which case a flow goes from a
+    /// slice with `flow_out` to the next slice with `flow_in`.
+    pub fn resolve(events: Vec<Event>, sync_tasks: &HashMap<SyncTaskId, CCT>) -> Self {
+        let mut edges = Vec::new();
+
+        let mut flows: HashMap<FlowId, Vec<Event>> = HashMap::new();
+        for event in events {
+            let id = (event.category.clone(), event.id_pid(), event.id().clone());
+            flows.entry(id).or_default().push(event);
+        }
+        for (_, mut events) in flows {
+            events.sort();
+            // ids are reused once a flow ends, so each start begins a flow of its own that goes
+            // on up to its end.
+            let mut last: Option<(&Event, Option<NodeRef>)> = None;
+            for event in &events {
+                let node = bind_flow_event(event, sync_tasks);
+                let from = match (&event.phase_type, last) {
+                    (EventPhase::FlowStart, _) => None,
+                    (_, Some(last)) => Some(last),
+                    (_, None) => {
+                        debug!("flow {} goes on with no start", event.id());
+                        continue;
+                    }
+                };
+                last = (event.phase_type != EventPhase::FlowEnd).then_some((event, node));
+
+                let Some((from_event, from)) = from else {
+                    continue;
+                };
+                let (Some(from), Some(to)) = (from, node) else {
+                    debug!("flow {} has no slice to bind to", event.id());
+                    continue;
+                };
+                edges.push(FlowEdge {
+                    id: from_event.id().clone(),
+                    category: from_event.category.clone(),
+                    name: from_event.name.clone(),
+                    from,
+                    to,
+                });
+            }
+        }
+
+        let mut bound: HashMap<Id, Vec<(NodeRef, &CCTNode)>> = HashMap::new();
+        for (task, cct) in sync_tasks {
+            for node in cct.into_iter().skip(1) {
+                let event = node.event();
+                if event.bind_id != Id::None && (event.flow_in || event.flow_out) {
+                    let node_ref = NodeRef {
+                        task: *task,
+                        node: node.id(),
+                    };
+                    bound
+                        .entry(event.bind_id.clone())
+                        .or_default()
+                        .push((node_ref, node));
+                }
+            }
+        }
+        for (id, mut nodes) in bound {
+            nodes.sort_by_key(|(node_ref, node)| (node.start_time(), *node_ref));
+            for pair in nodes.windows(2) {
+                let ((from, from_node), (to, to_node)) = (pair[0], pair[1]);
+                if from_node.event().flow_out && to_node.event().flow_in {
+                    edges.push(FlowEdge {
+                        id: id.clone(),
+                        category: from_node.event().category.clone(),
+                        name: from_node.event().name.clone(),
+                        from,
+                        to,
+                    });
+                }
+            }
+        }
+
+        // the order of the edges does not depend on the order of the hash maps
+        edges.sort_by_key(|edge| (edge.from, edge.to));
+        let mut graph = FlowGraph::default();
+        for edge in edges {
+            graph.push(edge);
+        }
+        graph
+    }
+
+    fn push(&mut self, edge: FlowEdge) {
+        let index = self.edges.len();
+        self.outgoing.entry(edge.from).or_default().push(index);
+        self.incoming.entry(edge.to).or_default().push(index);
+        self.edges.push(edge);
+    }
+
+    /// returns every edge of the graph.
+    pub fn edges(&self) -> &[FlowEdge] {
+        &self.edges
+    }
+
+    /// returns the edges of the flows going out of a node.
+    pub fn outgoing(&self, node: NodeRef) -> impl Iterator<Item = &FlowEdge> {
+        self.outgoing
+            .get(&node)
+            .into_iter()
+            .flatten()
+            .map(|i| &self.edges[*i])
+    }
+
+    /// returns the edges of the flows coming into a node.
+    pub fn incoming(&self, node: NodeRef) -> impl Iterator<Item = &FlowEdge> {
+        self.incoming
+            .get(&node)
+            .into_iter()
+            .flatten()
+            .map(|i| &self.edges[*i])
+    }
+}
+
+/// finds the node of its thread that a flow event binds to.
+fn bind_flow_event(event: &Event, sync_tasks: &HashMap<SyncTaskId, CCT>) -> Option<NodeRef> {
+    let task = (event.pid, event.tid);
+    let cct = sync_tasks.get(&task)?;
+    let node = match (&event.phase_type, event.binding_point.as_deref()) {
+        (EventPhase::FlowEnd, Some("e")) | (EventPhase::FlowStart | EventPhase::FlowStep, _) => {
+            cct.enclosing_slice(event.timestamp)
+        }
+        _ => cct.next_slice(event.timestamp),
+    }?;
+    Some(NodeRef {
+        task,
+        node: node.id(),
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use std::path::Path;
+
+    use super::NodeRef;
+    use crate::{build_application_cct, collect_traces};
+
+    #[test]
+    fn check_flows_bind_to_slices_of_other_threads() -> std::io::Result<()> {
+        let trace = collect_traces(Path::new("../data/trace-flows.json"))?;
+        let app_cct = build_application_cct(trace);
+
+        let node = |tid, node| NodeRef {
+            task: (1, tid),
+            node,
+        };
+        let name = |node_ref| app_cct.node(node_ref).unwrap().event().name.as_str();
+        let edges: Vec<_> = app_cct
+            .flows
+            .edges()
+            .iter()
+            .map(|edge| (name(edge.from), name(edge.to)))
+            .collect();
+        assert_eq!(
+            edges,
+            [
+                ("PostTask", "Relay"),
+                ("Send", "Receive"),
+                ("RunTask", "Reply"),
+                ("Relay", "RunTask"),
+                // the flow id is reused, but a flow does not go on from its end
+                ("x", "y"),
+                ("z", "w"),
+            ]
+        );
+
+        let relay = node(3, 1);
+        let incoming: Vec<_> = app_cct.flows.incoming(relay).map(|e| e.from).collect();
+        let outgoing: Vec<_> = app_cct.flows.outgoing(relay).map(|e| e.to).collect();
+        assert_eq!(incoming, [node(1, 1)]);
+        assert_eq!(outgoing, [node(2, 1)]);
+        assert_eq!(app_cct.flows.incoming(node(1, 1)).count(), 0);
+        Ok(())
+    }
+}
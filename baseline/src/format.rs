@@ -0,0 +1,505 @@
+use std::io::{Error, ErrorKind, Read, Result, Seek, SeekFrom};
+
+use serde::{de::IgnoredAny, Deserialize};
+
+use log::warn;
+
+use crate::{
+    ftrace::parse_system_trace,
+    schema::{check_event, Deviation, SchemaError},
+    Event, Trace,
+};
+
+/// Container denotes which of the two layouts of the trace event format a tracefile uses.
+/// the object layout holds the events in the `traceEvents` field of a json object, i.e.,
+/// `{"traceEvents": [...]}` while the array layout is just a bare `[...]` of events.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Container {
+    Object,
+    Array,
+}
+
+/// EventsStart points to the first byte after the `[` that opens the event array.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct EventsStart {
+    pub container: Container,
+    pub offset: u64,
+}
+
+/// locates the event array of a tracefile by reading as much of its beginning as needed.
+pub fn locate_events<R: Read>(reader: R) -> Result<EventsStart> {
+    let mut prefix = Vec::new();
+    let mut reader = reader;
+    loop {
+        let nbytes = reader.by_ref().take(64 * 1024).read_to_end(&mut prefix)?;
+        match find_events(&prefix) {
+            Ok(start) => return Ok(start),
+            Err(ScanError::Incomplete) if nbytes > 0 => continue,
+            Err(ScanError::Incomplete) => {
+                return Err(invalid_data("trace ended before its event array started"))
+            }
+            Err(ScanError::Invalid(msg)) => return Err(invalid_data(&msg)),
+        }
+    }
+}
+
+/// reads the fields of the trace object around its event array, for the readers that split the
+/// events between threads and skip the rest. `events_end` points to the `]` that closes the event
+/// array, unless the trace is truncated.
+/// the returned trace holds the fields along with the events of its system trace, if any.
+pub fn read_surroundings<R: Read + Seek>(
+    source: R,
+    start: EventsStart,
+    events_end: Option<u64>,
+) -> Result<Trace> {
+    if start.container == Container::Array {
+        return Ok(Trace::default());
+    }
+    let mut source = source;
+    let mut data = Vec::new();
+    source.seek(SeekFrom::Start(0))?;
+    source.by_ref().take(start.offset).read_to_end(&mut data)?;
+    match events_end {
+        Some(pos) => {
+            source.seek(SeekFrom::Start(pos))?;
+            source.read_to_end(&mut data)?;
+        }
+        None => data.push(b']'),
+    }
+    parse_trace(&data)
+}
+
+/// parses a whole tracefile in either of the container layouts.
+/// a trace that ends before its closing brackets, e.g. because the traced process crashed, is not
+/// an error. every complete event is recovered and the trace is marked as truncated.
+pub fn parse_trace(data: &[u8]) -> Result<Trace> {
+    parse(Scanner::new(data))
+}
+
+/// parses a whole tracefile like `parse_trace`, but fails with a `SchemaError` holding every
+/// deviation of the events from the strict schema, if there is any.
+pub fn parse_trace_strict(data: &[u8]) -> Result<Trace> {
+    let mut scanner = Scanner::new(data);
+    scanner.deviations = Some(Vec::new());
+    parse(scanner)
+}
+
+fn parse(mut scanner: Scanner) -> Result<Trace> {
+    let mut trace = Trace::default();
+
+    scanner.skip_whitespace();
+    let parsed = match scanner.peek() {
+        Some(b'[') => {
+            scanner.pos += 1;
+            parse_events(&mut scanner, &mut trace.events)
+        }
+        Some(b'{') => {
+            scanner.pos += 1;
+            parse_fields(&mut scanner, &mut trace)
+        }
+        Some(c) => {
+            return Err(invalid_data(&format!(
+                "expected a trace object or an event array, found `{}`",
+                c as char
+            )))
+        }
+        None => Err(ScanError::Incomplete),
+    };
+
+    match parsed {
+        Ok(()) => (),
+        Err(ScanError::Incomplete) => trace.truncated = true,
+        Err(ScanError::Invalid(msg)) => return Err(invalid_data(&msg)),
+    }
+    if !scanner.seen_events {
+        return Err(match trace.truncated {
+            true => invalid_data("trace ended before its event array started"),
+            false => invalid_data("missing field `traceEvents`"),
+        });
+    }
+
+    if let Some(deviations) = scanner.deviations.filter(|d| !d.is_empty()) {
+        return Err(Error::new(
+            ErrorKind::InvalidData,
+            SchemaError { deviations },
+        ));
+    }
+
+    if let Some(system_trace) = &trace.system_trace_events {
+        // kernel side markers are recorded apart from the events, so they are appended after
+        // them and put in order along with the events of their task when the CCTs are built.
+        trace.events.extend(parse_system_trace(system_trace));
+    }
+    Ok(trace)
+}
+
+/// parses the fields of the trace object up to and including its closing brace.
+fn parse_fields(scanner: &mut Scanner, trace: &mut Trace) -> std::result::Result<(), ScanError> {
+    loop {
+        scanner.skip_whitespace();
+        match scanner.peek() {
+            Some(b'}') => return Ok(()),
+            Some(b',') => {
+                scanner.pos += 1;
+                continue;
+            }
+            None => return Err(ScanError::Incomplete),
+            _ => (),
+        }
+        match &*scanner.key()? {
+            "traceEvents" => {
+                scanner.skip_whitespace();
+                match scanner.peek() {
+                    Some(b'[') => scanner.pos += 1,
+                    Some(_) => {
+                        return Err(ScanError::Invalid("`traceEvents` is not an array".into()))
+                    }
+                    None => return Err(ScanError::Incomplete),
+                }
+                parse_events(scanner, &mut trace.events)?;
+            }
+            "systemTraceEvents" => {
+                // besides ftrace text, this field might hold other formats (e.g. fuchsia's)
+                // which are not supported.
+                match scanner.value()? {
+                    serde_json::Value::String(text) => trace.system_trace_events = Some(text),
+                    _ => warn!("systemTraceEvents is not ftrace text and is ignored"),
+                }
+            }
+            "displayTimeUnit" => trace.fields.display_time_unit = Some(scanner.value()?),
+            "metadata" => trace.fields.metadata = Some(scanner.value()?),
+            "otherData" => trace.fields.other_data = Some(scanner.value()?),
+            "stackFrames" => trace.fields.stack_frames = Some(scanner.value()?),
+            "samples" => trace.fields.samples = Some(scanner.value()?),
+            "controllerTraceDataKey" => {
+                trace.fields.controller_trace_data_key = Some(scanner.value()?)
+            }
+            key => {
+                let value = scanner.value()?;
+                trace.fields.extra.insert(key.into(), value);
+            }
+        }
+    }
+}
+
+/// parses the elements of the event array into events until the array is closed.
+fn parse_events(
+    scanner: &mut Scanner,
+    events: &mut Vec<Event>,
+) -> std::result::Result<(), ScanError> {
+    scanner.seen_events = true;
+    let mut index = events.len();
+    loop {
+        scanner.skip_whitespace();
+        match scanner.peek() {
+            None => return Err(ScanError::Incomplete),
+            Some(b']') => {
+                scanner.pos += 1;
+                return Ok(());
+            }
+            _ => (),
+        }
+        let offset = scanner.pos as u64;
+        match scanner.deviations.is_some() {
+            true => {
+                let value: serde_json::Value = scanner.value()?;
+                let mut deviates = false;
+                if let Some(deviations) = &mut scanner.deviations {
+                    let found = deviations.len();
+                    check_event(index, offset, &value, deviations);
+                    deviates = deviations.len() > found;
+                }
+                match Event::deserialize(value) {
+                    Ok(event) => events.push(event),
+                    // the deviations of the event are reported in place of it
+                    Err(_) if deviates => (),
+                    Err(e) => {
+                        return Err(ScanError::Invalid(format!("event at byte {offset}: {e}")))
+                    }
+                }
+            }
+            false => events.extend(scanner.event()?),
+        }
+        index += 1;
+        scanner.skip_whitespace();
+        match scanner.peek() {
+            None => return Err(ScanError::Incomplete),
+            Some(b',') => scanner.pos += 1,
+            Some(b']') => (),
+            Some(c) => {
+                return Err(ScanError::Invalid(format!(
+                    "expected `,` or `]` after an event, found `{}`",
+                    c as char
+                )))
+            }
+        }
+    }
+}
+
+/// finds where the event array starts.
+fn find_events(data: &[u8]) -> std::result::Result<EventsStart, ScanError> {
+    let mut scanner = Scanner::new(data);
+    scanner.skip_whitespace();
+    match scanner.peek() {
+        Some(b'[') => {
+            return Ok(EventsStart {
+                container: Container::Array,
+                offset: scanner.pos as u64 + 1,
+            })
+        }
+        Some(b'{') => scanner.pos += 1,
+        Some(c) => {
+            return Err(ScanError::Invalid(format!(
+                "expected a trace object or an event array, found `{}`",
+                c as char
+            )))
+        }
+        None => return Err(ScanError::Incomplete),
+    }
+
+    loop {
+        scanner.skip_whitespace();
+        match scanner.peek() {
+            Some(b'}') => return Err(ScanError::Invalid("missing field `traceEvents`".into())),
+            Some(b',') => {
+                scanner.pos += 1;
+                continue;
+            }
+            _ => (),
+        }
+        let key = scanner.key()?;
+        if key == "traceEvents" {
+            scanner.skip_whitespace();
+            return match scanner.peek() {
+                Some(b'[') => Ok(EventsStart {
+                    container: Container::Object,
+                    offset: scanner.pos as u64 + 1,
+                }),
+                Some(_) => Err(ScanError::Invalid("`traceEvents` is not an array".into())),
+                None => Err(ScanError::Incomplete),
+            };
+        }
+        scanner.value::<IgnoredAny>()?;
+    }
+}
+
+fn invalid_data(msg: &str) -> Error {
+    Error::new(ErrorKind::InvalidData, msg)
+}
+
+/// ScanError tells apart data that is malformed from data that ends too early.
+enum ScanError {
+    Incomplete,
+    Invalid(String),
+}
+
+impl From<serde_json::Error> for ScanError {
+    fn from(e: serde_json::Error) -> Self {
+        if e.is_eof() {
+            ScanError::Incomplete
+        } else {
+            ScanError::Invalid(e.to_string())
+        }
+    }
+}
+
+/// Scanner walks over the json structure surrounding the events, deserializing one value at a
+/// time.
+struct Scanner<'a> {
+    data: &'a [u8],
+    pos: usize,
+    /// denotes whether the event array is reached.
+    seen_events: bool,
+    /// the deviations from the strict schema, which are only recorded by strict reads.
+    deviations: Option<Vec<Deviation>>,
+}
+
+impl<'a> Scanner<'a> {
+    fn new(data: &'a [u8]) -> Self {
+        Self {
+            data,
+            pos: 0,
+            seen_events: false,
+            deviations: None,
+        }
+    }
+
+    fn peek(&self) -> Option<u8> {
+        self.data.get(self.pos).copied()
+    }
+
+    fn skip_whitespace(&mut self) {
+        while let Some(b' ' | b'\n' | b'\r' | b'\t') = self.peek() {
+            self.pos += 1;
+        }
+    }
+
+    /// deserializes the json value starting at the current position and moves past it.
+    fn value<T: Deserialize<'a>>(&mut self) -> std::result::Result<T, ScanError> {
+        let mut stream = serde_json::Deserializer::from_slice(&self.data[self.pos..]).into_iter();
+        match stream.next() {
+            Some(Ok(value)) => {
+                self.pos += stream.byte_offset();
+                Ok(value)
+            }
+            Some(Err(e)) => Err(e.into()),
+            None => Err(ScanError::Incomplete),
+        }
+    }
+
+    /// deserializes the event starting at the current position and moves past it. an event
+    /// whose fields do not fit, e.g. a timestamp out of range, is skipped like the parallel
+    /// readers do.
+    fn event(&mut self) -> std::result::Result<Option<Event>, ScanError> {
+        let mut stream = serde_json::Deserializer::from_slice(&self.data[self.pos..]).into_iter();
+        match stream.next() {
+            Some(Ok(event)) => {
+                self.pos += stream.byte_offset();
+                Ok(Some(event))
+            }
+            Some(Err(e)) if e.is_data() => {
+                warn!("faced error when parsing event at byte {}: {e}", self.pos);
+                self.value::<IgnoredAny>()?;
+                Ok(None)
+            }
+            Some(Err(e)) => Err(e.into()),
+            None => Err(ScanError::Incomplete),
+        }
+    }
+
+    /// reads an object key along with its following colon.
+    fn key(&mut self) -> std::result::Result<String, ScanError> {
+        let key: String = self.value()?;
+        self.skip_whitespace();
+        match self.peek() {
+            Some(b':') => {
+                self.pos += 1;
+                Ok(key)
+            }
+            Some(_) => Err(ScanError::Invalid(format!(
+                "expected `:` after key `{key}`"
+            ))),
+            None => Err(ScanError::Incomplete),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::path::Path;
+
+    use utils::hash_to_int;
+
+    use crate::{collect_traces, collect_traces_strict, EventPhase, Problem, SchemaError};
+
+    #[test]
+    fn check_container_layouts_and_truncation() -> std::io::Result<()> {
+        let trace_object = collect_traces(Path::new("../data/trace-valid-ending.json"))?;
+        assert!(!trace_object.truncated);
+
+        let trace_array = collect_traces(Path::new("../data/trace-array.json"))?;
+        assert!(!trace_array.truncated);
+        assert_eq!(trace_object.events, trace_array.events);
+
+        let trace_truncated = collect_traces(Path::new("../data/trace-truncated.json"))?;
+        assert!(trace_truncated.truncated);
+        assert_eq!(trace_object.events[..40], trace_truncated.events[..]);
+        Ok(())
+    }
+
+    #[test]
+    fn check_lenient_and_strict_schemas() -> std::io::Result<()> {
+        let path = Path::new("../data/trace-lenient.json");
+        let trace = collect_traces(path)?;
+        let fields: Vec<_> = trace
+            .events
+            .iter()
+            .map(|e| {
+                (
+                    e.name.as_str(),
+                    e.category.as_str(),
+                    e.pid,
+                    e.tid,
+                    e.timestamp,
+                )
+            })
+            .collect();
+        let browser = hash_to_int("Browser");
+        assert_eq!(
+            fields,
+            [
+                ("process_name", "", 1, 0, 0),
+                ("clock_sync", "", 1, 1, 5_000),
+                // the tid that overflows is saturated
+                ("load", "net", 1, i64::MAX, 10_000),
+                ("paint", "gfx", browser, 2, 12_500),
+                ("paint", "gfx", browser, 2, 14_000),
+                // the event whose timestamp overflows is dropped
+                ("7", "gfx", 1, 0, 15_000),
+            ]
+        );
+        assert_eq!(trace.events[1].phase_type, EventPhase::Clock);
+
+        assert!(collect_traces_strict(Path::new("../data/trace-valid-ending.json")).is_ok());
+        let error = collect_traces_strict(path).unwrap_err();
+        let deviations = &error
+            .get_ref()
+            .and_then(|e| e.downcast_ref::<SchemaError>())
+            .unwrap()
+            .deviations;
+        let found: Vec<_> = deviations
+            .iter()
+            .map(|d| (d.index, d.field, d.problem.clone()))
+            .collect();
+        let wrong_type = |expected, found| Problem::WrongType { expected, found };
+        assert_eq!(
+            found,
+            [
+                (0, "cat", Problem::Missing),
+                (0, "tid", Problem::Missing),
+                (0, "ts", Problem::Missing),
+                (1, "cat", Problem::Missing),
+                (2, "pid", wrong_type("an integer", "a string")),
+                (2, "tid", Problem::OutOfRange),
+                (2, "ts", wrong_type("a number", "a string")),
+                (3, "pid", wrong_type("an integer", "a string")),
+                (4, "pid", wrong_type("an integer", "a string")),
+                (5, "ts", Problem::OutOfRange),
+                (6, "name", wrong_type("a string", "a number")),
+                (6, "pid", wrong_type("an integer", "a number")),
+                (6, "tid", wrong_type("an integer", "null")),
+            ]
+        );
+        assert_eq!(deviations[0].offset, 17);
+        assert_eq!(
+            deviations[5].to_string(),
+            "event 2 at byte 163: `tid` is out of range"
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn check_fields_around_the_events_are_kept() -> std::io::Result<()> {
+        let trace = collect_traces(Path::new("../data/trace-fields.json"))?;
+        assert_eq!(trace.events.len(), 2);
+
+        let fields = &trace.fields;
+        assert_eq!(fields.display_time_unit.as_deref(), Some("ns"));
+        assert_eq!(
+            fields.controller_trace_data_key.as_deref(),
+            Some("traceEvents")
+        );
+        assert_eq!(fields.capture_date(), Some("2024-1-15 10:02:11"));
+        assert_eq!(fields.product_version(), Some("Chrome/120.0.6099.109"));
+        // metadata is preferred over otherData
+        assert_eq!(fields.command_line(), Some("chrome --type=browser"));
+        assert_eq!(fields.stack_frames.as_ref().unwrap()["3"]["parent"], "2");
+        assert_eq!(fields.samples.as_ref().unwrap()[0]["sf"], 3);
+        assert!(fields.extra.contains_key("powerTraceAsString"));
+
+        let trace = collect_traces(Path::new("../data/trace-systrace.json"))?;
+        assert!(trace.system_trace_events.is_some());
+        assert_eq!(trace.fields.product_version(), Some("Chrome/129.0.6668.89"));
+        Ok(())
+    }
+}
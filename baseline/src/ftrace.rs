@@ -0,0 +1,276 @@
+use std::collections::HashMap;
+
+use log::debug;
+use serde_json::json;
+
+use crate::{Event, EventPhase, Id, IdValue, ProcessId, ThreadId, Timestamp};
+
+/// category of the events made from atrace markers.
+const ATRACE_CATEGORY: &str = "atrace";
+
+/// FtraceLine is a single line of the textual ftrace output, i.e.,
+/// `TASK-PID (TGID) [CPU] FLAGS TIMESTAMP: FUNCTION: DETAILS`
+/// where TGID and FLAGS are optional.
+#[derive(Debug, PartialEq)]
+struct FtraceLine<'a> {
+    tid: ThreadId,
+    tgid: Option<ProcessId>,
+    timestamp: Timestamp,
+    function: &'a str,
+    details: &'a str,
+}
+
+/// parses the ftrace text of a trace's `systemTraceEvents` into events.
+/// only the userspace markers written by atrace into `tracing_mark_write` are turned into
+/// events, i.e., `B|pid|name` and `E|pid` slices, `S|pid|name|cookie` and `F|pid|name|cookie`
+/// async slices, `I|pid|name` instants and `C|pid|name|value` counters.
+/// the rest of the kernel events are skipped.
+pub fn parse_system_trace(text: &str) -> Vec<Event> {
+    let mut events = Vec::new();
+
+    // `E` markers of older atrace versions do not carry the pid, so the pid is remembered from
+    // the thread's previous markers.
+    let mut pids: HashMap<ThreadId, ProcessId> = HashMap::new();
+
+    for line in text.lines() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let Some(line) = parse_line(line) else {
+            debug!("ftrace line {line} is not recognized");
+            continue;
+        };
+        if line.function != "tracing_mark_write" {
+            continue;
+        }
+        let Some(mut event) = parse_marker(line.details) else {
+            debug!("atrace marker {} is not recognized", line.details);
+            continue;
+        };
+        event.tid = line.tid;
+        event.timestamp = line.timestamp;
+        if event.pid == 0 {
+            event.pid = line
+                .tgid
+                .or_else(|| pids.get(&line.tid).copied())
+                .unwrap_or(line.tid);
+        }
+        pids.insert(event.tid, event.pid);
+        events.push(event);
+    }
+    events
+}
+
+/// splits an ftrace line into its parts.
+fn parse_line(line: &str) -> Option<FtraceLine<'_>> {
+    // the cpu column, e.g. `[001]`, separates the task from the timestamp
+    let cpu_start = line.match_indices('[').map(|(i, _)| i).find(|&i| {
+        line[i + 1..]
+            .split_once(']')
+            .is_some_and(|(cpu, _)| !cpu.is_empty() && cpu.bytes().all(|c| c.is_ascii_digit()))
+    })?;
+    let cpu_end = cpu_start + line[cpu_start..].find(']')?;
+
+    let mut task = line[..cpu_start].trim_end();
+    let mut tgid = None;
+    if task.ends_with(')') {
+        let (rest, group) = task.rsplit_once('(')?;
+        tgid = group.trim_end_matches(')').trim().parse().ok();
+        task = rest.trim_end();
+    }
+    let (_, tid) = task.rsplit_once('-')?;
+    let tid = tid.parse().ok()?;
+
+    let (head, rest) = line[cpu_end + 1..].split_once(": ")?;
+    let timestamp = parse_seconds(head.split_whitespace().last()?)?;
+    let (function, details) = rest.split_once(": ").unwrap_or((rest, ""));
+
+    Some(FtraceLine {
+        tid,
+        tgid,
+        timestamp,
+        function: function.trim(),
+        details: details.trim(),
+    })
+}
+
+/// converts an ftrace timestamp in seconds, e.g. `12345.678901`, into nanoseconds.
+/// the sign applies to the fraction as well, and timestamps that do not fit are rejected.
+fn parse_seconds(timestamp: &str) -> Option<Timestamp> {
+    let (negative, timestamp) = match timestamp.strip_prefix('-') {
+        Some(timestamp) => (true, timestamp),
+        None => (false, timestamp),
+    };
+    let (seconds, fraction) = timestamp.split_once('.').unwrap_or((timestamp, ""));
+    let is_digits = |digits: &str| digits.bytes().all(|b| b.is_ascii_digit());
+    if seconds.is_empty() || !is_digits(seconds) || !is_digits(fraction) {
+        return None;
+    }
+    // digits beyond nanoseconds are cut off
+    let fraction = format!("{:0<9}", &fraction[..fraction.len().min(9)]);
+    let nanos = seconds
+        .parse::<i64>()
+        .ok()?
+        .checked_mul(1_000_000_000)?
+        .checked_add(fraction.parse().ok()?)?;
+    Some(if negative { -nanos } else { nanos })
+}
+
+/// turns an atrace marker into an event whose pid is set if the marker carries it.
+fn parse_marker(marker: &str) -> Option<Event> {
+    let mut parts = marker.splitn(4, '|');
+    let kind = parts.next()?;
+    let pid = parts.next().and_then(|pid| pid.trim().parse().ok());
+    let name = parts.next().unwrap_or_default();
+    let rest = parts.next();
+
+    let mut event = Event {
+        name: name.into(),
+        category: ATRACE_CATEGORY.into(),
+        pid: pid.unwrap_or_default(),
+        ..Default::default()
+    };
+    match kind {
+        "B" => {
+            event.phase_type = EventPhase::SyncBegin;
+            // newer atrace versions append arguments after the name
+            if let Some(args) = rest {
+                event.name = format!("{name}|{args}");
+            }
+        }
+        "E" => event.phase_type = EventPhase::SyncEnd,
+        "I" => event.phase_type = EventPhase::SyncInstant,
+        "S" | "F" => {
+            event.phase_type = match kind {
+                "S" => EventPhase::AsyncBegin,
+                _ => EventPhase::AsyncEnd,
+            };
+            // cookies are only unique within their process
+            event.id = Id::Local(IdValue::Int(rest?.trim().parse().ok()?));
+        }
+        "C" => {
+            event.phase_type = EventPhase::Counter;
+            let value: f64 = rest?.trim().parse().ok()?;
+            event.args = Some(json!({ name: value }));
+        }
+        _ => return None,
+    }
+    Some(event)
+}
+
+#[cfg(test)]
+mod test {
+    use std::path::Path;
+
+    use super::{parse_line, parse_seconds, parse_system_trace, FtraceLine};
+    use crate::{build_application_cct, cct::verify, collect_traces, EventPhase, Id, IdValue};
+
+    #[test]
+    fn check_ftrace_lines_are_parsed() {
+        assert_eq!(
+            parse_line("surfaceflinger-598   (  598) [001] ...1  1234.567890: tracing_mark_write: B|598|onMessageReceived"),
+            Some(FtraceLine {
+                tid: 598,
+                tgid: Some(598),
+                timestamp: 1_234_567_890_000,
+                function: "tracing_mark_write",
+                details: "B|598|onMessageReceived",
+            })
+        );
+        assert_eq!(
+            parse_line("<...>-1234  [002] d..3 12.5: sched_switch: prev_comm=a prev_pid=1"),
+            Some(FtraceLine {
+                tid: 1234,
+                tgid: None,
+                timestamp: 12_500_000_000,
+                function: "sched_switch",
+                details: "prev_comm=a prev_pid=1",
+            })
+        );
+    }
+
+    #[test]
+    fn check_seconds_are_converted_to_nanoseconds() {
+        assert_eq!(parse_seconds("12"), Some(12_000_000_000));
+        assert_eq!(parse_seconds("0.0000000019"), Some(1));
+        assert_eq!(parse_seconds("-1.5"), Some(-1_500_000_000));
+        assert_eq!(parse_seconds("-0.25"), Some(-250_000_000));
+        assert_eq!(parse_seconds("1.5é"), None);
+        assert_eq!(parse_seconds("1.-5"), None);
+        assert_eq!(parse_seconds("--1"), None);
+        assert_eq!(parse_seconds(".5"), None);
+        assert_eq!(parse_seconds("9223372036854775807.0"), None);
+    }
+
+    #[test]
+    fn check_atrace_markers_become_events() {
+        let events = parse_system_trace(
+            "# tracer: nop\n\
+             RenderThread-7 [000] ...1 1.000001: tracing_mark_write: B|5|DrawFrame\n\
+             RenderThread-7 [000] ...1 1.000002: tracing_mark_write: C|5|queued|3\n\
+             RenderThread-7 [000] ...1 1.000003: tracing_mark_write: S|5|animation|42\n\
+             RenderThread-7 [000] ...1 1.000004: tracing_mark_write: E\n\
+             RenderThread-7 [000] ...1 1.000005: tracing_mark_write: F|5|animation|42\n\
+             RenderThread-7 [000] d..3 1.000006: sched_wakeup: comm=a pid=8\n",
+        );
+        let phases: Vec<_> = events.iter().map(|e| e.phase_type.clone()).collect();
+        assert_eq!(
+            phases,
+            [
+                EventPhase::SyncBegin,
+                EventPhase::Counter,
+                EventPhase::AsyncBegin,
+                EventPhase::SyncEnd,
+                EventPhase::AsyncEnd,
+            ]
+        );
+        assert!(events.iter().all(|e| e.pid == 5 && e.tid == 7));
+        assert_eq!(events[2].id, Id::Local(IdValue::Int(42)));
+        assert_eq!(events[1].args, Some(serde_json::json!({ "queued": 3.0 })));
+    }
+
+    #[test]
+    fn check_atrace_cookies_are_local_to_their_process() {
+        let events = parse_system_trace(
+            "RenderThread-7 [000] ...1 1.000001: tracing_mark_write: S|5|animation|1\n\
+             RenderThread-9 [001] ...1 1.000002: tracing_mark_write: S|6|animation|1\n\
+             RenderThread-7 [000] ...1 1.000003: tracing_mark_write: F|5|animation|1\n\
+             RenderThread-9 [001] ...1 1.000004: tracing_mark_write: F|6|animation|1\n",
+        );
+        let app_cct = build_application_cct(crate::Trace {
+            events,
+            ..Default::default()
+        });
+
+        let mut tasks: Vec<_> = app_cct
+            .async_tasks
+            .iter()
+            .map(|((_, pid, id, _), cct)| (*pid, id.clone(), cct.into_iter().count()))
+            .collect();
+        tasks.sort();
+        let cookie = Id::Local(IdValue::Int(1));
+        assert_eq!(tasks, [(Some(5), cookie.clone(), 2), (Some(6), cookie, 2)]);
+    }
+
+    #[test]
+    fn check_system_trace_events_join_thread_ccts() -> std::io::Result<()> {
+        let trace = collect_traces(Path::new("../data/trace-systrace.json"))?;
+        // the events of the system trace come after the others, which keep their order
+        let (json, system) = trace.events.split_at(trace.events.len() - 3);
+        assert_eq!(json[0].name, "process_name");
+        assert!(json.iter().all(|e| e.category != "atrace"));
+        assert!(system.iter().all(|e| e.category == "atrace"));
+
+        let app_cct = build_application_cct(trace);
+        let cct = &app_cct.sync_tasks[&(1201, 1)];
+        verify::assert_cct_valid(cct);
+        let binder = cct
+            .into_iter()
+            .find(|node| node.event().name == "binder transaction")
+            .unwrap();
+        let parent = cct.node(binder.parent_id().unwrap()).unwrap();
+        assert_eq!(parent.event().name, "ThreadControllerImpl::RunTask");
+        Ok(())
+    }
+}
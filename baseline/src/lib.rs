@@ -1,48 +1,167 @@
 mod application;
 mod cct;
+mod clock;
+mod counter;
+mod flow;
+mod format;
+mod ftrace;
+mod memory;
+mod metadata;
+mod object;
+mod profile;
+mod schema;
+mod split;
 mod trace;
+mod vitals;
 
 use std::fs::File;
-use std::io::BufReader;
-use std::io::Result;
+use std::io::{Read, Result, Seek};
 use std::path::Path;
 
-pub use application::ApplicationCCT;
+use log::warn;
+use memmap2::Mmap;
+use utils::Compression;
+
 use application::ApplicationTrace;
+pub use application::{ApplicationCCT, Markers, ObjectLifeCycleId, SyncTaskId};
+pub use memory::{Allocator, AllocatorDiff, MemoryDiff, MemoryDump, MemoryTimeline};
+pub use metadata::{Metadata, ProcessMeta, ThreadMeta};
+pub use object::{churn_by_type, diff, Change, JsonChange, ObjectHistory, Snapshot, TypeChurn};
+pub use profile::{Frame, Profile, ProfileNode, Profiles, StackFrames};
+
+pub use cct::{CCTMeta, CCTNode, CCT};
+pub use clock::{ClockOffset, ClockSync};
+pub use counter::{Counter, CounterId, CounterValues, Counters, Series};
 
-pub use cct::CCT;
+pub use flow::{FlowEdge, FlowGraph, NodeRef};
+pub use format::{locate_events, read_surroundings, Container, EventsStart};
+pub use schema::{Deviation, Problem, SchemaError};
+pub use split::{align_chunks, read_lines, split_chunk, split_slice, ChunkBounds};
 
-pub use trace::{Category, Id, ProcessId, Scope, ThreadId};
-pub use trace::{Event, EventPhase, Trace};
+pub use trace::{Category, Id, IdValue, ProcessId, Scope, ThreadId, Timestamp};
+pub use trace::{Event, EventPhase, InstantScope, Trace, TraceFields};
+pub use vitals::{Metric, Navigation, WebVitals};
 
 /// collect_traces reads a tracefile and construct a Trace
+/// gzip and zstd compressed tracefiles are decompressed on the fly.
+/// both the `{"traceEvents": [...]}` and the bare `[...]` layouts are accepted and a tracefile
+/// that ends abruptly yields all of its complete events.
 pub fn collect_traces(trace_path: &Path) -> Result<Trace> {
-    let data = File::open(trace_path)?;
-    let data = BufReader::new(data);
-    let trace: Trace = serde_json::from_reader(data)?;
+    let trace = map_trace(trace_path, format::parse_trace)?;
+    if trace.truncated {
+        warn!(
+            "trace {} is truncated, recovered {} events",
+            trace_path.to_string_lossy(),
+            trace.events.len()
+        );
+    }
+    Ok(trace)
+}
+
+/// collect_traces_strict reads a tracefile like collect_traces, but fails if any of its events
+/// departs from the trace event format. the error is a SchemaError that reports each deviation
+/// along with the location of its event.
+pub fn collect_traces_strict(trace_path: &Path) -> Result<Trace> {
+    map_trace(trace_path, format::parse_trace_strict)
+}
+
+/// maps a tracefile into memory and parses it. the file is read in place unless it is
+/// compressed, in which case only the decoded content is buffered.
+fn map_trace(trace_path: &Path, parse: fn(&[u8]) -> Result<Trace>) -> Result<Trace> {
+    let file = File::open(trace_path)?;
+    // SAFETY: the trace file is not expected to be modified while it is being read. if it is,
+    // the events read from it might be garbled, but they are still owned and valid.
+    let mapped = unsafe { Mmap::map(&file)? };
+    match Compression::from_magic(&mapped) {
+        Compression::None => parse(&mapped),
+        _ => parse(&utils::decode_to_vec(&mapped[..])?),
+    }
+}
+
+/// collect_traces_from_slice construct a Trace from a tracefile that is already in memory
+pub fn collect_traces_from_slice(data: &[u8]) -> Result<Trace> {
+    let trace = match Compression::from_magic(data) {
+        Compression::None => format::parse_trace(data)?,
+        _ => format::parse_trace(&utils::decode_to_vec(data)?)?,
+    };
+    if trace.truncated {
+        warn!(
+            "trace is truncated, recovered {} events",
+            trace.events.len()
+        );
+    }
     Ok(trace)
 }
 
+/// collect_traces_from_reader construct a Trace from the whole content of a seekable reader.
+/// the reader is read once from its current position to its end without seeking, just like
+/// collect_traces_from_stream does, since the baseline parses the trace sequentially anyway.
+/// the bound on `Seek` only keeps the signature in line with the parallel readers.
+pub fn collect_traces_from_reader<R: Read + Seek + Send>(reader: R) -> Result<Trace> {
+    collect_traces_from_stream(reader)
+}
+
+/// collect_traces_from_stream construct a Trace from a reader that cannot seek, e.g. stdin
+pub fn collect_traces_from_stream<R: Read>(reader: R) -> Result<Trace> {
+    collect_traces_from_slice(&utils::decode_to_vec(reader)?)
+}
+
 /// build_application_cct reads the Trace and creates the ApplicationCCT
 /// from the trace
-pub fn build_application_cct(trace: Trace) -> ApplicationCCT {
+pub fn build_application_cct(mut trace: Trace) -> ApplicationCCT {
     let mut app_trace = ApplicationTrace::new();
+    app_trace.fields = trace.fields;
+
+    // the clocks of the processes are aligned before the events are grouped, which might put
+    // the events of a task out of order when it spans processes, and so do the events of the
+    // system trace, which come after the others
+    app_trace.clock_sync = ClockSync::from_events(&trace.events);
+    for event in trace.events.iter_mut() {
+        app_trace.clock_sync.rebase(event);
+    }
+    let unordered = !app_trace.clock_sync.is_empty() || trace.system_trace_events.is_some();
 
     for event in trace.events.into_iter() {
         match event.phase_type {
+            EventPhase::SyncInstant if event.instant_scope != InstantScope::Thread => {
+                app_trace.markers.push(event)
+            }
+            EventPhase::Metadata => app_trace.metadata.push(event),
+            EventPhase::Counter => app_trace.counters.push(event),
+            EventPhase::Sample => app_trace.samples.push(event),
+            EventPhase::MemoryDumpGlobal | EventPhase::MemoryDumpProcess => {
+                app_trace.memory.push(event)
+            }
+            EventPhase::FlowStart | EventPhase::FlowStep | EventPhase::FlowEnd => {
+                app_trace.flows.push(event)
+            }
             EventPhase::SyncBegin
             | EventPhase::SyncEnd
             | EventPhase::SyncInstant
-            | EventPhase::Complete => {
+            | EventPhase::Complete
+            | EventPhase::Mark
+            | EventPhase::ContextEnter
+            | EventPhase::ContextLeave => {
                 let id = (event.pid, event.tid);
                 app_trace.sync_tasks.entry(id).or_default().push(event);
             }
-            EventPhase::AsyncBegin | EventPhase::AsyncEnd | EventPhase::AsyncInstant => {
-                let id = (event.scope.clone(), event.id, event.category.clone());
+            EventPhase::AsyncBegin
+            | EventPhase::AsyncEnd
+            | EventPhase::AsyncInstant
+            | EventPhase::AsyncStart
+            | EventPhase::AsyncStepInto
+            | EventPhase::AsyncStepPast
+            | EventPhase::AsyncFinish => {
+                let id = (
+                    event.scope.clone(),
+                    event.id_pid(),
+                    event.id().clone(),
+                    event.category.clone(),
+                );
                 app_trace.async_tasks.entry(id).or_default().push(event);
             }
             EventPhase::ObjectCreate | EventPhase::ObjectSnapshot | EventPhase::ObjectDestroy => {
-                let id = (event.scope.clone(), event.id);
+                let id = (event.scope.clone(), event.id_pid(), event.id().clone());
                 app_trace
                     .object_life_cycle
                     .entry(id)
@@ -52,5 +171,175 @@ pub fn build_application_cct(trace: Trace) -> ApplicationCCT {
             _ => (),
         }
     }
+    if unordered {
+        app_trace.sort();
+    }
     app_trace.application_cct()
 }
+
+#[cfg(test)]
+mod test {
+    use std::{fs::File, path::Path};
+
+    use crate::{Id, IdValue};
+
+    #[test]
+    fn check_traces_are_read_from_memory_and_readers() -> std::io::Result<()> {
+        let trace_file = super::collect_traces(Path::new("../data/trace-valid-ending.json"))?;
+
+        let data = std::fs::read("../data/trace-valid-ending.json.gz")?;
+        let trace_slice = super::collect_traces_from_slice(&data)?;
+        assert_eq!(trace_file.events, trace_slice.events);
+
+        let reader = File::open("../data/trace-valid-ending.json")?;
+        let trace_reader = super::collect_traces_from_reader(reader)?;
+        assert_eq!(trace_file.events, trace_reader.events);
+
+        let stream = File::open("../data/trace-valid-ending.json.zst")?;
+        let trace_stream = super::collect_traces_from_stream(stream)?;
+        assert_eq!(trace_file.events, trace_stream.events);
+        Ok(())
+    }
+
+    #[test]
+    fn check_async_ids_keep_their_scoping() -> std::io::Result<()> {
+        let trace = super::collect_traces_from_slice(
+            br#"[
+            {"name":"a","cat":"c","ph":"b","pid":1,"tid":1,"ts":1,"id2":{"local":"0x1f"}},
+            {"name":"a","cat":"c","ph":"b","pid":2,"tid":1,"ts":2,"id2":{"local":"0x1f"}},
+            {"name":"a","cat":"c","ph":"e","pid":1,"tid":1,"ts":3,"id2":{"local":"0x1f"}},
+            {"name":"a","cat":"c","ph":"e","pid":2,"tid":1,"ts":4,"id2":{"local":"0x1f"}},
+            {"name":"g","cat":"c","ph":"b","pid":1,"tid":1,"ts":5,"id2":{"global":"0x1f"}},
+            {"name":"g","cat":"c","ph":"e","pid":2,"tid":1,"ts":6,"id2":{"global":31}},
+            {"name":"r","cat":"c","ph":"b","pid":1,"tid":1,"ts":7,"id":"request-42"},
+            {"name":"r","cat":"c","ph":"b","pid":1,"tid":1,"ts":8,"id":"request-43"},
+            {"name":"r","cat":"c","ph":"e","pid":1,"tid":1,"ts":9,"id":"request-42"},
+            {"name":"r","cat":"c","ph":"e","pid":1,"tid":1,"ts":10,"id":"request-43"}
+            ]"#,
+        )?;
+        let app_cct = super::build_application_cct(trace);
+
+        let mut tasks: Vec<_> = app_cct
+            .async_tasks
+            .iter()
+            .map(|((_, pid, id, _), cct)| (*pid, id.clone(), cct.into_iter().count()))
+            .collect();
+        tasks.sort();
+        let local = Id::Local(IdValue::Int(0x1f));
+        let request = |id: &str| Id::Global(IdValue::Str(id.into()));
+        assert_eq!(
+            tasks,
+            [
+                (None, Id::Global(IdValue::Int(0x1f)), 2),
+                (None, request("request-42"), 2),
+                (None, request("request-43"), 2),
+                (Some(1), local.clone(), 2),
+                (Some(2), local, 2),
+            ]
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn check_ids_keep_their_value() -> std::io::Result<()> {
+        let trace = super::collect_traces_from_slice(
+            br#"[
+            {"name":"a","ph":"b","pid":1,"ts":1,"id":7,"id2":{"local":"0x1f"}},
+            {"name":"a","ph":"b","pid":1,"ts":2,"id":-1},
+            {"name":"a","ph":"b","pid":1,"ts":3,"id":18446744073709551615},
+            {"name":"a","ph":"b","pid":1,"ts":4,"id":1.5},
+            {"name":"a","ph":"b","pid":1,"ts":5,"id":2.0}
+            ]"#,
+        )?;
+        let ids: Vec<_> = trace.events.iter().map(|e| e.id().clone()).collect();
+        assert_eq!(
+            ids,
+            [
+                Id::Local(IdValue::Int(0x1f)),
+                Id::Global(IdValue::Str("-1".into())),
+                Id::Global(IdValue::Int(u64::MAX)),
+                Id::Global(IdValue::Str("1.5".into())),
+                Id::Global(IdValue::Int(2)),
+            ]
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn check_scoped_instants_become_markers() -> std::io::Result<()> {
+        let trace = super::collect_traces(Path::new("../data/trace-markers.json"))?;
+        let app_cct = super::build_application_cct(trace);
+
+        let names = |cct: &crate::CCT| -> Vec<String> {
+            cct.into_iter()
+                .skip(1)
+                .map(|node| node.event().name.clone())
+                .collect()
+        };
+        assert_eq!(
+            names(&app_cct.sync_tasks[&(1, 1)]),
+            ["Task", "ScheduleWork"]
+        );
+        assert_eq!(names(&app_cct.sync_tasks[&(1, 2)]), ["Task"]);
+        assert!(!app_cct.sync_tasks.contains_key(&(2, 7)));
+
+        let within = |pid, start, stop| -> Vec<&str> {
+            app_cct
+                .markers
+                .within(pid, start, stop)
+                .into_iter()
+                .map(|event| event.name.as_str())
+                .collect()
+        };
+        assert_eq!(
+            within(1, 1_000, 5_000),
+            ["NavigationStart", "TracingStarted"]
+        );
+        assert_eq!(
+            within(1, 1_000, 9_000),
+            ["NavigationStart", "TracingStarted", "FirstPaint"]
+        );
+        assert_eq!(within(2, 0, 9_000), ["TracingStarted"]);
+        assert!(within(1, 6_000, 7_000).is_empty());
+        Ok(())
+    }
+
+    #[test]
+    fn check_metadata_names_and_orders_tasks() -> std::io::Result<()> {
+        let trace = super::collect_traces_from_slice(
+            br#"[
+            {"name":"process_name","ph":"M","pid":1,"tid":0,"args":{"name":"Browser"}},
+            {"name":"process_sort_index","ph":"M","pid":1,"tid":0,"args":{"sort_index":-5}},
+            {"name":"process_labels","ph":"M","pid":1,"tid":0,"args":{"labels":"Inbox, Gmail"}},
+            {"name":"process_uptime_seconds","ph":"M","pid":1,"tid":0,"args":{"uptime":12.5}},
+            {"name":"thread_name","ph":"M","pid":1,"tid":2,"args":{"name":"IO"}},
+            {"name":"thread_sort_index","ph":"M","pid":1,"tid":2,"args":{"sort_index":-1}},
+            {"name":"num_cpus","ph":"M","pid":1,"tid":0,"args":{"number":8}},
+            {"name":"a","cat":"c","ph":"X","pid":1,"tid":1,"ts":1,"dur":1},
+            {"name":"a","cat":"c","ph":"X","pid":1,"tid":2,"ts":1,"dur":1},
+            {"name":"a","cat":"c","ph":"X","pid":0,"tid":1,"ts":1,"dur":1}
+            ]"#,
+        )?;
+        let app_cct = super::build_application_cct(trace);
+
+        let browser = &app_cct.metadata.processes[&1];
+        assert_eq!(browser.name.as_deref(), Some("Browser"));
+        assert_eq!(browser.labels, ["Inbox", "Gmail"]);
+        assert_eq!(browser.uptime_seconds, Some(12.5));
+        assert_eq!(app_cct.metadata.other[0].name, "num_cpus");
+
+        let cct = &app_cct.sync_tasks[&(1, 2)];
+        assert_eq!(cct.metadata().process_name(), Some("Browser"));
+        assert_eq!(cct.metadata().thread_name(), Some("IO"));
+        assert!(format!("{cct}").starts_with("context meta: Browser|IO"));
+        assert_eq!(app_cct.sync_tasks[&(1, 1)].metadata().thread_name(), None);
+
+        let order: Vec<_> = app_cct
+            .sorted_sync_tasks()
+            .into_iter()
+            .map(|(id, _)| *id)
+            .collect();
+        assert_eq!(order, [(1, 2), (1, 1), (0, 1)]);
+        Ok(())
+    }
+}
@@ -0,0 +1,296 @@
+use std::collections::{BTreeMap, HashMap};
+
+use serde_json::Value;
+
+use crate::{ClockSync, Event, EventPhase, Id, ProcessId, Timestamp};
+
+/// Allocator is an entry of a memory dump, e.g. `malloc` or `v8/isolate_0/heap`, whose
+/// sub-allocations are the entries under its name, e.g. `malloc/allocated_objects`.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Allocator {
+    /// the bytes the allocator holds.
+    pub size: Option<u64>,
+    /// the bytes the allocator holds that are not accounted for by another allocator, which is
+    /// the size itself unless the dump tells otherwise.
+    pub effective_size: Option<u64>,
+}
+
+/// MemoryDump is a snapshot of the memory of a process, given by a `v` event, or of every
+/// process at once, given by a `V` event.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct MemoryDump {
+    /// the id shared by the dumps of every process that were taken at once.
+    pub id: Id,
+    pub pid: ProcessId,
+    pub timestamp: Timestamp,
+    /// e.g. `light`, `background` or `detailed`.
+    pub level_of_detail: Option<String>,
+    /// the totals of the process, e.g. `resident_set_bytes` and `private_footprint_bytes`.
+    pub process_totals: HashMap<String, u64>,
+    pub allocators: BTreeMap<String, Allocator>,
+}
+
+impl MemoryDump {
+    /// reads a memory dump event, where the dump is under `args.dumps` and the sizes are given
+    /// as hex strings.
+    pub fn from_event(event: &Event) -> Self {
+        let dumps = event.args.as_ref().and_then(|args| args.get("dumps"));
+        let get = |key| dumps.and_then(|dumps| dumps.get(key));
+
+        let process_totals = get("process_totals")
+            .and_then(Value::as_object)
+            .into_iter()
+            .flatten()
+            .filter_map(|(key, value)| Some((key.clone(), parse_size(value)?)))
+            .collect();
+        let allocators = get("allocators")
+            .and_then(Value::as_object)
+            .into_iter()
+            .flatten()
+            .map(|(name, allocator)| {
+                let attr = |key| {
+                    let attr = allocator.get("attrs")?.get(key)?;
+                    parse_size(attr.get("value").unwrap_or(attr))
+                };
+                let size = attr("size");
+                let allocator = Allocator {
+                    size,
+                    effective_size: attr("effective_size").or(size),
+                };
+                (name.clone(), allocator)
+            })
+            .collect();
+
+        MemoryDump {
+            id: event.id().clone(),
+            pid: event.pid,
+            timestamp: event.timestamp,
+            level_of_detail: get("level_of_detail")
+                .and_then(Value::as_str)
+                .map(String::from),
+            process_totals,
+            allocators,
+        }
+    }
+
+    pub fn allocator(&self, name: &str) -> Option<&Allocator> {
+        self.allocators.get(name)
+    }
+
+    /// the allocators directly under the one of the given name.
+    pub fn sub_allocations<'a>(
+        &'a self,
+        name: &'a str,
+    ) -> impl Iterator<Item = (&'a str, &'a Allocator)> {
+        let prefix = format!("{name}/");
+        self.allocators
+            .range(prefix.clone()..)
+            .take_while(move |(child, _)| child.starts_with(&prefix))
+            .filter(move |(child, _)| !child[name.len() + 1..].contains('/'))
+            .map(|(child, allocator)| (child.as_str(), allocator))
+    }
+
+    /// how the allocators changed from this dump to a later one. an allocator missing from
+    /// either dump counts as holding nothing in it.
+    pub fn diff(&self, later: &MemoryDump) -> MemoryDiff {
+        let mut allocators = BTreeMap::new();
+        for name in self.allocators.keys().chain(later.allocators.keys()) {
+            let size = |dump: &MemoryDump, size: fn(&Allocator) -> Option<u64>| {
+                dump.allocator(name).and_then(size).unwrap_or_default() as i64
+            };
+            let diff = AllocatorDiff {
+                size: size(later, |a| a.size) - size(self, |a| a.size),
+                effective_size: size(later, |a| a.effective_size)
+                    - size(self, |a| a.effective_size),
+            };
+            allocators.insert(name.clone(), diff);
+        }
+        MemoryDiff {
+            from: self.timestamp,
+            to: later.timestamp,
+            allocators,
+        }
+    }
+}
+
+/// AllocatorDiff is how many bytes an allocator gained, or lost if negative, between two dumps.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct AllocatorDiff {
+    pub size: i64,
+    pub effective_size: i64,
+}
+
+/// MemoryDiff is how the allocators of a process changed between two of its dumps.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct MemoryDiff {
+    pub from: Timestamp,
+    pub to: Timestamp,
+    pub allocators: BTreeMap<String, AllocatorDiff>,
+}
+
+impl MemoryDiff {
+    /// the allocators that changed, ordered by how much they grew, the most first.
+    pub fn growth(&self) -> Vec<(&str, &AllocatorDiff)> {
+        let mut growth: Vec<_> = self
+            .allocators
+            .iter()
+            .filter(|(_, diff)| diff.size != 0 || diff.effective_size != 0)
+            .map(|(name, diff)| (name.as_str(), diff))
+            .collect();
+        growth.sort_by_key(|(_, diff)| -diff.size);
+        growth
+    }
+}
+
+/// MemoryTimeline gathers the memory dumps of a trace, i.e., the events of phases `V` and `v`,
+/// as a timeline per process.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct MemoryTimeline {
+    pub global: Vec<MemoryDump>,
+    pub processes: HashMap<ProcessId, Vec<MemoryDump>>,
+}
+
+impl MemoryTimeline {
+    /// reads a memory dump event into the timeline of its process, or the global one.
+    pub fn push(&mut self, event: Event) {
+        let dump = MemoryDump::from_event(&event);
+        match event.phase_type {
+            EventPhase::MemoryDumpGlobal => self.global.push(dump),
+            _ => self.processes.entry(event.pid).or_default().push(dump),
+        }
+    }
+
+    /// moves the dumps of another MemoryTimeline after the ones already in this one.
+    pub fn append(&mut self, mut other: Self) {
+        self.global.append(&mut other.global);
+        for (pid, mut dumps) in other.processes.into_iter() {
+            self.processes.entry(pid).or_default().append(&mut dumps)
+        }
+    }
+
+    /// moves the dumps onto the clock of the reference.
+    pub fn rebase(&mut self, clock_sync: &ClockSync) {
+        let dumps = self
+            .global
+            .iter_mut()
+            .chain(self.processes.values_mut().flatten());
+        for dump in dumps {
+            dump.timestamp = clock_sync.rebase_timestamp(dump.pid, dump.timestamp);
+        }
+    }
+
+    /// puts every timeline in order of time.
+    pub fn sort(&mut self) {
+        self.global.sort_by_key(|dump| dump.timestamp);
+        for dumps in self.processes.values_mut() {
+            dumps.sort_by_key(|dump| dump.timestamp);
+        }
+    }
+
+    /// the dumps of a process, in order of time.
+    pub fn dumps(&self, pid: ProcessId) -> &[MemoryDump] {
+        self.processes.get(&pid).map_or(&[], Vec::as_slice)
+    }
+
+    /// the dump of a process nearest to the span from `start` up to `stop`, where the dumps
+    /// within the span are the nearest and the earliest of those is taken.
+    pub fn nearest(
+        &self,
+        pid: ProcessId,
+        start: Timestamp,
+        stop: Timestamp,
+    ) -> Option<&MemoryDump> {
+        self.dumps(pid).iter().min_by_key(|dump| {
+            if dump.timestamp < start {
+                start - dump.timestamp
+            } else {
+                (dump.timestamp - stop).max(0)
+            }
+        })
+    }
+
+    /// how the memory of a process changed over the span from `start` up to `stop`, i.e., from
+    /// the last dump up to `start` to the first dump from `stop` on.
+    pub fn diff_over(
+        &self,
+        pid: ProcessId,
+        start: Timestamp,
+        stop: Timestamp,
+    ) -> Option<MemoryDiff> {
+        let dumps = self.dumps(pid);
+        let before = dumps.iter().rev().find(|dump| dump.timestamp <= start)?;
+        let after = dumps.iter().find(|dump| dump.timestamp >= stop)?;
+        Some(before.diff(after))
+    }
+}
+
+/// parses a size, which is usually a hex string but might be a number.
+fn parse_size(value: &Value) -> Option<u64> {
+    match value {
+        Value::Number(n) => n.as_u64(),
+        Value::String(s) => {
+            let s = s.trim();
+            u64::from_str_radix(s.strip_prefix("0x").unwrap_or(s), 16).ok()
+        }
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::path::Path;
+
+    use crate::{build_application_cct, collect_traces};
+
+    #[test]
+    fn check_memory_dumps_form_a_timeline_per_process() -> std::io::Result<()> {
+        let trace = collect_traces(Path::new("../data/trace-memory.json"))?;
+        let app_cct = build_application_cct(trace);
+        let memory = &app_cct.memory;
+        assert_eq!(memory.global.len(), 1);
+        assert_eq!(memory.dumps(2).len(), 1);
+
+        let dumps = memory.dumps(1);
+        assert_eq!(dumps.len(), 2);
+        let first = &dumps[0];
+        assert_eq!(first.level_of_detail.as_deref(), Some("detailed"));
+        assert_eq!(first.process_totals["resident_set_bytes"], 0x1000);
+        assert_eq!(first.allocator("malloc").unwrap().size, Some(0x1000));
+        let v8 = first.allocator("v8").unwrap();
+        assert_eq!((v8.size, v8.effective_size), (Some(0x400), Some(0x300)));
+
+        let sub_allocations: Vec<_> = dumps[1]
+            .sub_allocations("malloc")
+            .map(|(name, allocator)| (name, allocator.size))
+            .collect();
+        assert_eq!(
+            sub_allocations,
+            [
+                ("malloc/allocated_objects", Some(0x1500)),
+                ("malloc/metadata_fragmentation_caches", Some(0x100)),
+            ]
+        );
+
+        let parse = app_cct.sync_tasks[&(1, 1)].node(1).unwrap();
+        assert_eq!(app_cct.memory_dump_near(parse), Some(first));
+
+        let diff = app_cct.memory_diff_over(parse).unwrap();
+        assert_eq!((diff.from, diff.to), (95000, 160000));
+        let growth: Vec<_> = diff
+            .growth()
+            .into_iter()
+            .map(|(name, diff)| (name, diff.size))
+            .collect();
+        assert_eq!(
+            growth,
+            [
+                ("malloc/allocated_objects", 0xd00),
+                ("malloc", 0x800),
+                ("partition_alloc", 0x200),
+                ("malloc/metadata_fragmentation_caches", 0x100),
+                ("malloc/allocated_objects/strings", 0x10),
+            ]
+        );
+        Ok(())
+    }
+}
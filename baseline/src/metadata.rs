@@ -0,0 +1,105 @@
+use std::collections::HashMap;
+
+use serde_json::Value;
+
+use crate::{application::SyncTaskId, Event, ProcessId, ThreadId};
+
+/// ProcessMeta holds what the metadata events tell about a process.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ProcessMeta {
+    pub name: Option<String>,
+    /// the position of the process among the others when shown, lower ones first.
+    pub sort_index: Option<i64>,
+    pub labels: Vec<String>,
+    pub uptime_seconds: Option<f64>,
+}
+
+/// ThreadMeta holds what the metadata events tell about a thread.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ThreadMeta {
+    pub name: Option<String>,
+    /// the position of the thread among the others of its process when shown.
+    pub sort_index: Option<i64>,
+}
+
+/// Metadata gathers the metadata events of a trace, i.e., the ones of phase `M`, per process
+/// and per thread. the metadata events that are not about a process or a thread, e.g.
+/// `num_cpus`, are kept as they are.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Metadata {
+    pub processes: HashMap<ProcessId, ProcessMeta>,
+    pub threads: HashMap<SyncTaskId, ThreadMeta>,
+    pub other: Vec<Event>,
+}
+
+impl Metadata {
+    /// records a metadata event. a later event overrides what an earlier one told.
+    pub fn push(&mut self, event: Event) {
+        let arg = |key: &str| event.args.as_ref().and_then(|args| args.get(key));
+        let string_arg = |key| arg(key).and_then(Value::as_str).map(String::from);
+        let int_arg = |key| arg(key).and_then(Value::as_i64);
+
+        match &*event.name {
+            "process_name" => {
+                let process = self.process(event.pid);
+                process.name = string_arg("name").or(process.name.take());
+            }
+            "process_sort_index" => {
+                let process = self.process(event.pid);
+                process.sort_index = int_arg("sort_index").or(process.sort_index);
+            }
+            "process_labels" => {
+                // the labels are a single comma separated string
+                let labels = string_arg("labels").unwrap_or_default();
+                self.process(event.pid).labels.extend(
+                    labels
+                        .split(',')
+                        .map(str::trim)
+                        .filter(|label| !label.is_empty())
+                        .map(String::from),
+                );
+            }
+            "process_uptime_seconds" => {
+                let process = self.process(event.pid);
+                process.uptime_seconds = arg("uptime")
+                    .and_then(Value::as_f64)
+                    .or(process.uptime_seconds);
+            }
+            "thread_name" => {
+                let thread = self.thread(event.pid, event.tid);
+                thread.name = string_arg("name").or(thread.name.take());
+            }
+            "thread_sort_index" => {
+                let thread = self.thread(event.pid, event.tid);
+                thread.sort_index = int_arg("sort_index").or(thread.sort_index);
+            }
+            _ => self.other.push(event),
+        }
+    }
+
+    /// moves the metadata of another Metadata into this one, as if its events were pushed after
+    /// the ones already in this one.
+    pub fn append(&mut self, other: Self) {
+        for (pid, meta) in other.processes {
+            let process = self.process(pid);
+            process.name = meta.name.or(process.name.take());
+            process.sort_index = meta.sort_index.or(process.sort_index);
+            process.labels.extend(meta.labels);
+            process.uptime_seconds = meta.uptime_seconds.or(process.uptime_seconds);
+        }
+        for ((pid, tid), meta) in other.threads {
+            let thread = self.thread(pid, tid);
+            thread.name = meta.name.or(thread.name.take());
+            thread.sort_index = meta.sort_index.or(thread.sort_index);
+        }
+        self.other.extend(other.other);
+    }
+
+    fn process(&mut self, pid: ProcessId) -> &mut ProcessMeta {
+        self.processes.entry(pid).or_default()
+    }
+
+    fn thread(&mut self, pid: ProcessId, tid: ThreadId) -> &mut ThreadMeta {
+        self.threads.entry((pid, tid)).or_default()
+    }
+}
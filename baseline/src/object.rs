@@ -0,0 +1,259 @@
+use std::collections::HashMap;
+
+use serde_json::Value;
+
+use crate::{application::ObjectLifeCycleId, EventPhase, Timestamp, CCT};
+
+/// Snapshot is the state of an object at a point in time, as given by the `args.snapshot` of a
+/// snapshot event.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Snapshot<'a> {
+    pub timestamp: Timestamp,
+    pub value: &'a Value,
+}
+
+/// Change is how a part of an object differs from one snapshot to the next.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Change<'a> {
+    Added(&'a Value),
+    Removed(&'a Value),
+    Changed { from: &'a Value, to: &'a Value },
+}
+
+/// JsonChange is a change along with where it is, as a JSON pointer, e.g. `/layers/0/bounds`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct JsonChange<'a> {
+    pub path: String,
+    pub change: Change<'a>,
+}
+
+/// returns the structural differences between two JSON values. objects are compared key by key
+/// and arrays item by item, down to the values that differ.
+pub fn diff<'a>(from: &'a Value, to: &'a Value) -> Vec<JsonChange<'a>> {
+    let mut changes = Vec::new();
+    diff_at(String::new(), from, to, &mut changes);
+    changes
+}
+
+fn diff_at<'a>(path: String, from: &'a Value, to: &'a Value, changes: &mut Vec<JsonChange<'a>>) {
+    match (from, to) {
+        (Value::Object(from), Value::Object(to)) => {
+            for (key, from_value) in from {
+                let path = format!("{path}/{}", key.replace('~', "~0").replace('/', "~1"));
+                match to.get(key) {
+                    Some(to_value) => diff_at(path, from_value, to_value, changes),
+                    None => changes.push(JsonChange {
+                        path,
+                        change: Change::Removed(from_value),
+                    }),
+                }
+            }
+            for (key, to_value) in to.iter().filter(|(key, _)| !from.contains_key(*key)) {
+                let path = format!("{path}/{}", key.replace('~', "~0").replace('/', "~1"));
+                changes.push(JsonChange {
+                    path,
+                    change: Change::Added(to_value),
+                });
+            }
+        }
+        (Value::Array(from), Value::Array(to)) => {
+            for (i, (from_value, to_value)) in from.iter().zip(to).enumerate() {
+                diff_at(format!("{path}/{i}"), from_value, to_value, changes);
+            }
+            for (i, from_value) in from.iter().enumerate().skip(to.len()) {
+                changes.push(JsonChange {
+                    path: format!("{path}/{i}"),
+                    change: Change::Removed(from_value),
+                });
+            }
+            for (i, to_value) in to.iter().enumerate().skip(from.len()) {
+                changes.push(JsonChange {
+                    path: format!("{path}/{i}"),
+                    change: Change::Added(to_value),
+                });
+            }
+        }
+        _ if from != to => changes.push(JsonChange {
+            path,
+            change: Change::Changed { from, to },
+        }),
+        _ => (),
+    }
+}
+
+/// ObjectHistory is a lifetime of an object, from its creation to its destruction, along with
+/// the snapshots taken of it in between. an object whose creation is not traced, e.g. because
+/// it was created before tracing started, is only known by its snapshots.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ObjectHistory<'a> {
+    pub id: &'a ObjectLifeCycleId,
+    /// the type of the object, e.g. `LayerTreeHostImpl`.
+    pub name: &'a str,
+    pub created: Option<Timestamp>,
+    pub destroyed: Option<Timestamp>,
+    /// the snapshots in order of time.
+    pub snapshots: Vec<Snapshot<'a>>,
+}
+
+impl<'a> ObjectHistory<'a> {
+    /// reads the lifetimes of an object from its object life cycle CCT, in order of time.
+    /// an id that is reused after the object is destroyed gives a lifetime per object.
+    pub fn from_cct(id: &'a ObjectLifeCycleId, cct: &'a CCT) -> Vec<Self> {
+        let mut histories: Vec<Self> = Vec::new();
+        // the lifetime of each creation node, and of the root for the snapshots outside of any
+        let mut lifetimes: HashMap<usize, usize> = HashMap::new();
+        for node in cct.into_iter().skip(1) {
+            let event = node.event();
+            match event.phase_type {
+                EventPhase::ObjectCreate => {
+                    lifetimes.insert(node.id(), histories.len());
+                    histories.push(ObjectHistory {
+                        id,
+                        name: &event.name,
+                        created: Some(node.start_time()),
+                        destroyed: node.stop_time(),
+                        snapshots: Vec::new(),
+                    });
+                }
+                EventPhase::ObjectSnapshot => {
+                    let parent = node.parent_id().unwrap_or_default();
+                    let index = *lifetimes.entry(parent).or_insert_with(|| {
+                        histories.push(ObjectHistory {
+                            id,
+                            name: &event.name,
+                            created: None,
+                            destroyed: None,
+                            snapshots: Vec::new(),
+                        });
+                        histories.len() - 1
+                    });
+                    let value = event.args.as_ref().and_then(|args| args.get("snapshot"));
+                    if let Some(value) = value {
+                        histories[index].snapshots.push(Snapshot {
+                            timestamp: node.start_time(),
+                            value,
+                        });
+                    }
+                }
+                _ => (),
+            }
+        }
+        histories
+    }
+
+    /// returns how long the object lived, if both its creation and its destruction are traced.
+    pub fn lifetime(&self) -> Option<Timestamp> {
+        Some(self.destroyed? - self.created?)
+    }
+
+    /// returns the differences between each snapshot and the next.
+    pub fn diffs(&self) -> Vec<Vec<JsonChange<'a>>> {
+        self.snapshots
+            .windows(2)
+            .map(|pair| diff(pair[0].value, pair[1].value))
+            .collect()
+    }
+
+    /// returns the number of changes across the snapshots.
+    pub fn churn(&self) -> usize {
+        self.diffs().iter().map(Vec::len).sum()
+    }
+}
+
+/// TypeChurn is how much the objects of a type changed across their snapshots.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct TypeChurn {
+    pub name: String,
+    pub objects: usize,
+    pub snapshots: usize,
+    pub changes: usize,
+}
+
+/// returns the churn of each type of object, the most changed first.
+pub fn churn_by_type(histories: &[ObjectHistory]) -> Vec<TypeChurn> {
+    let mut types: HashMap<&str, TypeChurn> = HashMap::new();
+    for history in histories {
+        let churn = types.entry(history.name).or_insert_with(|| TypeChurn {
+            name: history.name.to_string(),
+            ..Default::default()
+        });
+        churn.objects += 1;
+        churn.snapshots += history.snapshots.len();
+        churn.changes += history.churn();
+    }
+    let mut types: Vec<_> = types.into_values().collect();
+    types.sort_by(|a, b| b.changes.cmp(&a.changes).then_with(|| a.name.cmp(&b.name)));
+    types
+}
+
+#[cfg(test)]
+mod test {
+    use std::path::Path;
+
+    use serde_json::json;
+
+    use super::{Change, TypeChurn};
+    use crate::{build_application_cct, collect_traces};
+
+    #[test]
+    fn check_object_snapshots_are_diffed() -> std::io::Result<()> {
+        let trace = collect_traces(Path::new("../data/trace-objects.json"))?;
+        let app_cct = build_application_cct(trace);
+
+        let histories = app_cct.object_histories();
+        let lifetimes: Vec<_> = histories
+            .iter()
+            .map(|history| (history.name, history.lifetime(), history.snapshots.len()))
+            .collect();
+        assert_eq!(
+            lifetimes,
+            [
+                ("Frame", Some(49_000), 0),
+                // the layer is created before tracing started
+                ("Layer", None, 2),
+                ("Document", Some(10_000), 2),
+                ("Document", Some(10_000), 1),
+            ]
+        );
+
+        let document = &histories[2];
+        assert_eq!(document.snapshots[1].timestamp, 14_000);
+        let diffs = document.diffs();
+        assert_eq!(diffs.len(), 1);
+        let changes: Vec<_> = diffs[0]
+            .iter()
+            .map(|change| (change.path.as_str(), change.change.clone()))
+            .collect();
+        let (one, two, three, title) = (json!(1), json!(2), json!(3), json!("t"));
+        assert_eq!(
+            changes,
+            [
+                ("/children/1", Change::Added(&two)),
+                (
+                    "/nodes",
+                    Change::Changed {
+                        from: &one,
+                        to: &three
+                    }
+                ),
+                ("/title", Change::Added(&title)),
+            ]
+        );
+
+        let churn = |name: &str, objects, snapshots, changes| TypeChurn {
+            name: name.into(),
+            objects,
+            snapshots,
+            changes,
+        };
+        assert_eq!(
+            app_cct.snapshot_churn(),
+            [
+                churn("Document", 2, 3, 3),
+                churn("Layer", 1, 2, 1),
+                churn("Frame", 1, 0, 0),
+            ]
+        );
+        Ok(())
+    }
+}
@@ -0,0 +1,298 @@
+use std::collections::{HashMap, HashSet};
+
+use log::debug;
+use serde::Deserialize;
+use serde_json::Value;
+
+use crate::{application::SyncTaskId, Event, ProcessId, TraceFields};
+
+/// Frame is an entry of the `stackFrames` table, which points to the frame that called it.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Frame {
+    pub name: String,
+    pub category: String,
+    pub parent: Option<String>,
+}
+
+/// StackFrames is the `stackFrames` table of a trace, where the frames are given by their id.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct StackFrames {
+    pub frames: HashMap<String, Frame>,
+}
+
+impl StackFrames {
+    /// reads the table from the `stackFrames` field of a trace. frames that are not objects are
+    /// left out.
+    pub fn from_value(value: &Value) -> Self {
+        let Value::Object(table) = value else {
+            return Default::default();
+        };
+        let string = |frame: &Value, key| match frame.get(key) {
+            Some(Value::String(s)) => Some(s.clone()),
+            Some(Value::Number(n)) => Some(n.to_string()),
+            _ => None,
+        };
+        let frames = table
+            .iter()
+            .filter(|(_, frame)| frame.is_object())
+            .map(|(id, frame)| {
+                let frame = Frame {
+                    name: string(frame, "name").unwrap_or_default(),
+                    category: string(frame, "category").unwrap_or_default(),
+                    parent: string(frame, "parent"),
+                };
+                (id.clone(), frame)
+            })
+            .collect();
+        StackFrames { frames }
+    }
+
+    /// the stack whose leaf is the frame of the given id, from the root frame.
+    /// a frame whose parent is not in the table is taken as the root, and so is a frame whose
+    /// parent is already on the stack.
+    pub fn stack(&self, id: &str) -> Vec<&Frame> {
+        let mut stack = Vec::new();
+        let mut seen = HashSet::new();
+        let mut next = Some(id);
+        while let Some(id) = next {
+            let Some(frame) = self.frames.get(id) else {
+                break;
+            };
+            if !seen.insert(id) {
+                debug!("stack frame {id} loops back to itself");
+                break;
+            }
+            stack.push(frame);
+            next = frame.parent.as_deref();
+        }
+        stack.reverse();
+        stack
+    }
+}
+
+/// ProfileNode is a calling context of a sampled CCT, i.e., a frame reached through the frames
+/// above it. the count and weight are of the samples whose stack passes through the node, while
+/// the self count and weight are of the ones whose stack ends at it.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ProfileNode {
+    id: usize,
+    parent_node_id: Option<usize>,
+    children: Vec<usize>,
+    pub name: String,
+    pub category: String,
+    pub count: u64,
+    pub weight: f64,
+    pub self_count: u64,
+    pub self_weight: f64,
+}
+
+impl ProfileNode {
+    fn new(id: usize, parent_node_id: Option<usize>, name: &str, category: &str) -> Self {
+        ProfileNode {
+            id,
+            parent_node_id,
+            children: Vec::new(),
+            name: name.to_string(),
+            category: category.to_string(),
+            count: 0,
+            weight: 0.0,
+            self_count: 0,
+            self_weight: 0.0,
+        }
+    }
+
+    pub fn id(&self) -> usize {
+        self.id
+    }
+
+    pub fn parent_id(&self) -> Option<usize> {
+        self.parent_node_id
+    }
+}
+
+/// Profile is the calling context tree of the samples of a thread, where the first node is the
+/// root holding every sample.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Profile {
+    nodes: Vec<ProfileNode>,
+}
+
+impl Default for Profile {
+    fn default() -> Self {
+        Profile {
+            nodes: vec![ProfileNode::new(0, None, "", "")],
+        }
+    }
+}
+
+impl Profile {
+    pub fn root(&self) -> &ProfileNode {
+        &self.nodes[0]
+    }
+
+    pub fn node(&self, id: usize) -> Option<&ProfileNode> {
+        self.nodes.get(id)
+    }
+
+    pub fn children<'a>(&'a self, node: &'a ProfileNode) -> impl Iterator<Item = &'a ProfileNode> {
+        node.children.iter().map(|id| &self.nodes[*id])
+    }
+
+    /// the node reached from the root through frames of the given names.
+    pub fn find(&self, names: &[&str]) -> Option<&ProfileNode> {
+        names.iter().try_fold(self.root(), |node, name| {
+            self.children(node).find(|child| child.name == *name)
+        })
+    }
+
+    /// adds a sample of the given stack, from the root frame, to the tree.
+    fn add(&mut self, stack: &[(&str, &str)], weight: f64) {
+        let mut node_id = 0;
+        for (name, category) in stack {
+            self.nodes[node_id].count += 1;
+            self.nodes[node_id].weight += weight;
+            let child =
+                self.nodes[node_id].children.iter().copied().find(|id| {
+                    self.nodes[*id].name == *name && self.nodes[*id].category == *category
+                });
+            node_id = match child {
+                Some(child) => child,
+                None => {
+                    let id = self.nodes.len();
+                    let node = ProfileNode::new(id, Some(node_id), name, category);
+                    self.nodes.push(node);
+                    self.nodes[node_id].children.push(id);
+                    id
+                }
+            };
+        }
+        let leaf = &mut self.nodes[node_id];
+        leaf.count += 1;
+        leaf.weight += weight;
+        leaf.self_count += 1;
+        leaf.self_weight += weight;
+    }
+}
+
+impl<'a> IntoIterator for &'a Profile {
+    type Item = &'a ProfileNode;
+
+    type IntoIter = std::slice::Iter<'a, ProfileNode>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.nodes.iter()
+    }
+}
+
+/// Profiles holds the sampled CCT of every thread that has samples, next to the CCTs built from
+/// the instrumentation events.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Profiles {
+    pub stack_frames: StackFrames,
+    pub threads: HashMap<SyncTaskId, Profile>,
+}
+
+impl Profiles {
+    /// resolves the stacks of the samples into a sampled CCT per thread.
+    /// `events` are the sample events, i.e., of phase `P`, which count once each, while the
+    /// `samples` table of the trace gives the weight of each of its samples. its samples only
+    /// tell their thread, so their process is the one of the thread among `threads`, if any.
+    pub fn resolve(events: Vec<Event>, fields: &TraceFields, threads: &[SyncTaskId]) -> Self {
+        let stack_frames = fields
+            .stack_frames
+            .as_ref()
+            .map(StackFrames::from_value)
+            .unwrap_or_default();
+
+        let mut samples: Vec<(Event, f64)> = events.into_iter().map(|e| (e, 1.0)).collect();
+        let table = fields.samples.as_ref().and_then(Value::as_array);
+        for sample in table.into_iter().flatten() {
+            let Ok(mut event) = Event::deserialize(sample) else {
+                debug!("sample {sample} is not an event");
+                continue;
+            };
+            event.pid = sample_pid(sample, &event, threads);
+            let weight = sample.get("weight").and_then(Value::as_f64).unwrap_or(1.0);
+            samples.push((event, weight));
+        }
+        // the trees do not depend on the order the samples are read in
+        samples.sort_by_key(|(event, _)| (event.pid, event.tid, event.timestamp));
+
+        let mut threads: HashMap<SyncTaskId, Profile> = HashMap::new();
+        for (event, weight) in samples {
+            let stack: Vec<(&str, &str)> = match &event.stack_frame {
+                Some(id) => stack_frames
+                    .stack(id)
+                    .into_iter()
+                    .map(|frame| (frame.name.as_str(), frame.category.as_str()))
+                    .collect(),
+                None => event.stack.iter().map(|pc| (pc.as_str(), "")).collect(),
+            };
+            if stack.is_empty() {
+                debug!("sample {} has no stack", event.name);
+                continue;
+            }
+            let profile = threads.entry((event.pid, event.tid)).or_default();
+            profile.add(&stack, weight);
+        }
+
+        Profiles {
+            stack_frames,
+            threads,
+        }
+    }
+}
+
+/// the process of a sample of the `samples` table, which is the one of its thread among
+/// `threads` unless the sample tells it.
+pub(crate) fn sample_pid(sample: &Value, event: &Event, threads: &[SyncTaskId]) -> ProcessId {
+    if sample.get("pid").is_none() {
+        let mut pids = threads.iter().filter(|(_, tid)| *tid == event.tid);
+        if let (Some((pid, _)), None) = (pids.next(), pids.next()) {
+            return *pid;
+        }
+    }
+    event.pid
+}
+
+#[cfg(test)]
+mod test {
+    use std::path::Path;
+
+    use crate::{build_application_cct, collect_traces};
+
+    #[test]
+    fn check_samples_form_a_sampled_cct_per_thread() -> std::io::Result<()> {
+        let trace = collect_traces(Path::new("../data/trace-profile.json"))?;
+        let app_cct = build_application_cct(trace);
+        let profiles = &app_cct.profiles;
+        assert_eq!(profiles.threads.len(), 2);
+
+        let main = &profiles.threads[&(1, 1)];
+        let counts = |names: &[&str]| {
+            let node = main.find(names).unwrap();
+            (node.count, node.self_count)
+        };
+        assert_eq!(main.root().count, 5);
+        assert_eq!(counts(&["main", "RunTask"]), (3, 0));
+        assert_eq!(counts(&["main", "RunTask", "Paint"]), (2, 2));
+        assert_eq!(counts(&["main", "RunTask", "Layout"]), (1, 1));
+        assert_eq!(counts(&["0x10", "0x20"]), (1, 1));
+        // the frames of a loop are taken up to where they loop back
+        assert_eq!(counts(&["Wait", "Spin"]), (1, 1));
+
+        // the samples of the table only tell their thread, which is known by its metadata
+        let compositor = &profiles.threads[&(1, 2)];
+        let run_task = compositor.find(&["main", "RunTask"]).unwrap();
+        assert_eq!(compositor.root().weight, 7.0);
+        assert_eq!((run_task.count, run_task.weight), (2, 7.0));
+        assert_eq!((run_task.self_count, run_task.self_weight), (1, 2.0));
+        let paint = compositor.children(run_task).next().unwrap();
+        assert_eq!(
+            (paint.name.as_str(), paint.category.as_str()),
+            ("Paint", "gfx")
+        );
+        assert_eq!(compositor.node(paint.parent_id().unwrap()), Some(run_task));
+        Ok(())
+    }
+}
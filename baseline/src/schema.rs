@@ -0,0 +1,164 @@
+use std::fmt::Display;
+
+use serde_json::{Map, Value};
+
+use crate::EventPhase;
+
+/// Deviation is a place where an event departs from the strict schema of the trace event
+/// format, i.e., a field that is missing or is coerced while being read leniently.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Deviation {
+    /// position of the event within the event array.
+    pub index: usize,
+    /// byte offset of the event within the tracefile, after decompression.
+    pub offset: u64,
+    pub field: &'static str,
+    pub problem: Problem,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Problem {
+    NotAnObject,
+    Missing,
+    WrongType {
+        expected: &'static str,
+        found: &'static str,
+    },
+    OutOfRange,
+    UnknownPhase(String),
+}
+
+impl Display for Deviation {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "event {} at byte {}: ", self.index, self.offset)?;
+        match &self.problem {
+            Problem::NotAnObject => write!(f, "is not an object"),
+            Problem::Missing => write!(f, "`{}` is missing", self.field),
+            Problem::WrongType { expected, found } => {
+                write!(f, "`{}` is {found} instead of {expected}", self.field)
+            }
+            Problem::OutOfRange => write!(f, "`{}` is out of range", self.field),
+            Problem::UnknownPhase(code) => {
+                write!(f, "`{}` is the unknown phase {code:?}", self.field)
+            }
+        }
+    }
+}
+
+/// SchemaError is the error of a strict read, carrying every deviation of the trace.
+#[derive(Debug)]
+pub struct SchemaError {
+    pub deviations: Vec<Deviation>,
+}
+
+impl Display for SchemaError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{} deviations from the trace event format",
+            self.deviations.len()
+        )?;
+        for deviation in &self.deviations {
+            write!(f, "\n{deviation}")?;
+        }
+        Ok(())
+    }
+}
+
+impl std::error::Error for SchemaError {}
+
+/// checks an event against the strict schema and records its deviations.
+pub fn check_event(index: usize, offset: u64, event: &Value, deviations: &mut Vec<Deviation>) {
+    let mut deviate = |field, problem| {
+        deviations.push(Deviation {
+            index,
+            offset,
+            field,
+            problem,
+        })
+    };
+    let Value::Object(fields) = event else {
+        deviate("", Problem::NotAnObject);
+        return;
+    };
+
+    for field in ["name", "cat"] {
+        if let Some(problem) = check_field(fields, field, true, Value::is_string, "a string") {
+            deviate(field, problem);
+        }
+    }
+    match fields.get("ph") {
+        None => deviate("ph", Problem::Missing),
+        Some(Value::String(code)) => {
+            if let EventPhase::Unknown(code) = EventPhase::from_code(code) {
+                deviate("ph", Problem::UnknownPhase(code));
+            }
+        }
+        Some(value) => deviate("ph", wrong_type("a string", value)),
+    }
+    for field in ["pid", "tid"] {
+        match fields.get(field) {
+            Some(Value::Number(n)) if n.is_i64() => (),
+            Some(Value::Number(n)) if n.is_u64() => deviate(field, Problem::OutOfRange),
+            Some(value) => deviate(field, wrong_type("an integer", value)),
+            None => deviate(field, Problem::Missing),
+        }
+    }
+    for (field, required) in [
+        ("ts", true),
+        ("dur", false),
+        ("tts", false),
+        ("tdur", false),
+    ] {
+        match check_field(fields, field, required, Value::is_number, "a number") {
+            Some(problem) => deviate(field, problem),
+            None if fields.get(field).is_some_and(|time| !fits_nanos(time)) => {
+                deviate(field, Problem::OutOfRange)
+            }
+            None => (),
+        }
+    }
+    if let Some(problem) = check_field(fields, "scope", false, Value::is_string, "a string") {
+        deviate("scope", problem);
+    }
+}
+
+/// checks the type of a field, which is only reported missing if it is required.
+fn check_field(
+    fields: &Map<String, Value>,
+    field: &str,
+    required: bool,
+    is_expected: fn(&Value) -> bool,
+    expected: &'static str,
+) -> Option<Problem> {
+    match fields.get(field) {
+        Some(value) if is_expected(value) => None,
+        Some(value) => Some(wrong_type(expected, value)),
+        None if required => Some(Problem::Missing),
+        None => None,
+    }
+}
+
+/// denotes whether a time in microseconds is within the range of nanosecond timestamps.
+fn fits_nanos(time: &Value) -> bool {
+    match time {
+        Value::Number(n) if n.is_f64() => n.as_f64().is_some_and(utils::fits_nanos),
+        Value::Number(n) => n
+            .as_i64()
+            .and_then(|micros| micros.checked_mul(utils::NANOS_PER_MICRO))
+            .is_some(),
+        _ => true,
+    }
+}
+
+fn wrong_type(expected: &'static str, found: &Value) -> Problem {
+    let found = match found {
+        Value::Null => "null",
+        Value::Bool(_) => "a boolean",
+        Value::Number(_) => "a number",
+        Value::String(_) => "a string",
+        Value::Array(_) => "an array",
+        Value::Object(_) => "an object",
+    };
+    Problem::WrongType { expected, found }
+}
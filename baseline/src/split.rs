@@ -0,0 +1,528 @@
+use std::io::{BufRead, BufReader, Read, Result, Seek, SeekFrom};
+
+use log::{debug, warn};
+use serde::{de::IgnoredAny, Deserialize};
+
+use crate::Event;
+
+/// size of the blocks a chunk of the tracefile is read in.
+const BLOCK_SIZE: u64 = 64 * 1024;
+
+/// ChunkBounds tells where the events of a chunk of the event array are.
+/// a chunk owns every event that starts before its `end`, beginning from where the previous chunk
+/// stopped, so the chunks of a tracefile hold each of its events exactly once.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ChunkBounds {
+    /// offset of the first event of the chunk, or of whatever follows it if it holds none.
+    pub start: u64,
+    /// offset at which the byte range assigned to the chunk ends.
+    pub end: u64,
+    /// offset at which the chunk stopped, i.e., where the events of the next chunk start.
+    pub next: u64,
+    /// denotes whether the event array is closed within the chunk.
+    pub closed: bool,
+}
+
+/// splits the events that start within `[start, end)` of the tracefile and hands the raw bytes of
+/// each of them to `on_event`.
+/// the events are found by following the json structure, so neither the layout of the tracefile
+/// nor the brackets and commas within strings matter.
+/// if `start` is not `aligned`, i.e., it is not known to be right before an event, it might point
+/// anywhere within the event array and the first event is searched for. the search is a guess that
+/// has to be confirmed with [`align_chunks`].
+pub fn split_chunk<R, F>(
+    source: R,
+    start: u64,
+    end: u64,
+    aligned: bool,
+    on_event: F,
+) -> Result<ChunkBounds>
+where
+    R: Read + Seek,
+    F: FnMut(&[u8]),
+{
+    match aligned {
+        true => split(ReadWindow::new(source, start)?, end, None, on_event),
+        // the byte before the chunk is needed to tell whether the chunk starts with an event
+        false => split(
+            ReadWindow::new(source, start.saturating_sub(1))?,
+            end,
+            Some(start),
+            on_event,
+        ),
+    }
+}
+
+/// splits the events that start within `[start, end)` of a tracefile that is entirely in memory,
+/// e.g. mapped into it, just like [`split_chunk`] but without copying any of it.
+pub fn split_slice<F>(
+    data: &[u8],
+    start: u64,
+    end: u64,
+    aligned: bool,
+    on_event: F,
+) -> Result<ChunkBounds>
+where
+    F: FnMut(&[u8]),
+{
+    match aligned {
+        true => split(SliceWindow::new(data, start), end, None, on_event),
+        false => split(
+            SliceWindow::new(data, start.saturating_sub(1)),
+            end,
+            Some(start),
+            on_event,
+        ),
+    }
+}
+
+/// splits the events from the current byte of the window up to `end`.
+/// if the chunk is not aligned, `unaligned_start` is where the chunk starts, while the window
+/// starts a byte earlier.
+fn split<W, F>(
+    mut window: W,
+    end: u64,
+    unaligned_start: Option<u64>,
+    mut on_event: F,
+) -> Result<ChunkBounds>
+where
+    W: Window,
+    F: FnMut(&[u8]),
+{
+    if let Some(start) = unaligned_start {
+        find_event(&mut window, start == 0)?;
+    }
+    let start = window.position();
+
+    loop {
+        window.skip_separators()?;
+        let pos = window.position();
+        match window.peek()? {
+            None => break,
+            Some(b']') => {
+                return Ok(ChunkBounds {
+                    start,
+                    end,
+                    next: pos,
+                    closed: true,
+                })
+            }
+            Some(_) if pos >= end => break,
+            Some(c) => match window.scan()? {
+                Scan::Complete(len) => {
+                    on_event(&window.rest()[..len]);
+                    window.consume(len);
+                }
+                // the tracefile ends in the middle of an event
+                Scan::Incomplete => break,
+                Scan::Invalid => {
+                    warn!(
+                        "faced error when splitting events: unexpected `{}` at {pos}",
+                        c as char
+                    );
+                    window.consume(1);
+                }
+            },
+        }
+    }
+    Ok(ChunkBounds {
+        start,
+        end,
+        next: window.position(),
+        closed: false,
+    })
+}
+
+/// reads the lines that start within `[start, end)` of a newline delimited tracefile, i.e., one
+/// with a complete event on each line, and extracts the events from them.
+/// the returned flag denotes whether the last line of the tracefile is cut short.
+pub fn read_lines<R: Read + Seek>(source: R, start: u64, end: u64) -> Result<(Vec<Event>, bool)> {
+    let mut events = Vec::new();
+    let mut source = source;
+    let mut truncated = false;
+
+    // a line that starts right at `start` belongs to this chunk, the one that starts before it
+    // belongs to the previous chunk. so the reading starts one byte earlier and the rest of the
+    // previous chunk's line is skipped.
+    let mut pos = start.saturating_sub(1);
+    source.seek(SeekFrom::Start(pos))?;
+    let mut data = BufReader::new(source);
+
+    let mut buf = Vec::with_capacity(1500);
+    if start > 0 {
+        pos += data.read_until(b'\n', &mut buf)? as u64;
+        buf.clear();
+    }
+
+    while pos < end {
+        let nbytes = data.read_until(b'\n', &mut buf)?;
+        if nbytes == 0 {
+            break;
+        }
+        pos += nbytes as u64;
+
+        if buf.iter().all(u8::is_ascii_whitespace) {
+            buf.clear();
+            continue;
+        }
+        match serde_json::from_slice::<Event>(&buf) {
+            Ok(event) => events.push(event),
+            Err(e) => {
+                truncated = e.is_eof() && !buf.ends_with(b"\n");
+                warn!(
+                    "faced error when parsing {}: {e}",
+                    String::from_utf8_lossy(&buf)
+                );
+            }
+        }
+        buf.clear();
+    }
+    Ok((events, truncated))
+}
+
+/// confirms where each chunk starts, in order, since a chunk has to start right where the
+/// previous one stopped.
+/// a chunk that guessed wrong, e.g. because it started within a string that looked like an event,
+/// is read again from the right offset by calling `reread` with its new start and its end.
+/// the chunks that come after the end of the event array are emptied.
+pub fn align_chunks<T, F>(chunks: &mut [(T, ChunkBounds)], reread: F) -> Result<()>
+where
+    T: Default,
+    F: Fn(u64, u64) -> Result<(T, ChunkBounds)>,
+{
+    for i in 1..chunks.len() {
+        let previous = chunks[i - 1].1;
+        let bounds = chunks[i].1;
+        if previous.closed {
+            chunks[i] = (
+                T::default(),
+                ChunkBounds {
+                    start: previous.next,
+                    next: previous.next,
+                    closed: true,
+                    ..bounds
+                },
+            );
+        } else if bounds.start != previous.next {
+            debug!(
+                "chunk {i} guessed its first event at {} instead of {}",
+                bounds.start, previous.next
+            );
+            chunks[i] = reread(previous.next, bounds.end)?;
+        }
+    }
+    Ok(())
+}
+
+/// moves the window to the first byte that looks like the start of an event, or to the end of the
+/// tracefile if there is none.
+/// an event is a `{` that follows a `,` and opens an object with a `ph` field, which is followed by
+/// either a `,` or the `]` closing the event array.
+fn find_event<W: Window>(window: &mut W, at_start: bool) -> Result<()> {
+    // whether the current byte might come right after a `,`.
+    // whitespace before the chunk leaves it unknown, which is taken as a yes.
+    let mut after_comma = at_start;
+    if !at_start {
+        if let Some(c) = window.peek()? {
+            after_comma = c == b',' || c.is_ascii_whitespace();
+            window.consume(1);
+        }
+    }
+
+    while let Some(c) = window.peek()? {
+        if c == b'{' && after_comma {
+            match window.scan()? {
+                Scan::Complete(len) => {
+                    let is_event = serde_json::from_slice::<Probe>(&window.rest()[..len]).is_ok();
+                    if is_event && matches!(window.peek_after(len)?, None | Some(b',' | b']')) {
+                        return Ok(());
+                    }
+                }
+                // the tracefile ends in the middle of what might be its last event
+                Scan::Incomplete => return Ok(()),
+                Scan::Invalid => (),
+            }
+        }
+        if !c.is_ascii_whitespace() {
+            after_comma = c == b',';
+        }
+        window.consume(1);
+    }
+    Ok(())
+}
+
+/// Probe is the least that a json object needs to be taken as an event while searching for one.
+#[derive(Deserialize)]
+struct Probe {
+    #[serde(rename = "ph")]
+    _phase_type: IgnoredAny,
+}
+
+/// Scan is the outcome of looking for the end of a json value.
+#[derive(Debug, PartialEq, Eq)]
+enum Scan {
+    /// the value is complete and takes the given number of bytes.
+    Complete(usize),
+    /// the data ends before the value does.
+    Incomplete,
+    /// the data does not start with a json value.
+    Invalid,
+}
+
+/// finds the end of the json value at the beginning of `data` without deserializing it.
+/// brackets are only counted outside of strings, and quotes that are escaped do not end a string.
+fn scan_value(data: &[u8]) -> Scan {
+    let mut depth = 0usize;
+    let mut in_string = false;
+    let mut escaped = false;
+    for (i, &c) in data.iter().enumerate() {
+        if in_string {
+            match c {
+                _ if escaped => escaped = false,
+                b'\\' => escaped = true,
+                b'"' => {
+                    in_string = false;
+                    if depth == 0 {
+                        return Scan::Complete(i + 1);
+                    }
+                }
+                _ => (),
+            }
+            continue;
+        }
+        match c {
+            b'"' => in_string = true,
+            b'{' | b'[' => depth += 1,
+            // a number or a literal ends at the first byte that is not part of it
+            b'}' | b']' | b',' | b':' | b' ' | b'\n' | b'\r' | b'\t' if depth == 0 => {
+                return match i {
+                    0 => Scan::Invalid,
+                    _ => Scan::Complete(i),
+                }
+            }
+            b'}' | b']' => {
+                depth -= 1;
+                if depth == 0 {
+                    return Scan::Complete(i + 1);
+                }
+            }
+            _ => (),
+        }
+    }
+    Scan::Incomplete
+}
+
+/// Window is the part of the tracefile that is being split.
+trait Window {
+    /// offset of the current byte in the tracefile.
+    fn position(&self) -> u64;
+
+    /// the bytes that are available but not consumed yet.
+    fn rest(&self) -> &[u8];
+
+    fn consume(&mut self, nbytes: usize);
+
+    /// makes more of the tracefile available, returns false once the tracefile is exhausted.
+    fn fill(&mut self) -> Result<bool>;
+
+    fn peek(&mut self) -> Result<Option<u8>> {
+        loop {
+            match self.rest().first().copied() {
+                Some(c) => return Ok(Some(c)),
+                None if self.fill()? => continue,
+                None => return Ok(None),
+            }
+        }
+    }
+
+    /// returns the first byte that is not whitespace, `nbytes` or more after the current one.
+    fn peek_after(&mut self, nbytes: usize) -> Result<Option<u8>> {
+        let mut i = nbytes;
+        loop {
+            match self.rest().get(i).copied() {
+                Some(c) if c.is_ascii_whitespace() => i += 1,
+                Some(c) => return Ok(Some(c)),
+                None if self.fill()? => continue,
+                None => return Ok(None),
+            }
+        }
+    }
+
+    /// moves past the whitespace and commas between events.
+    fn skip_separators(&mut self) -> Result<()> {
+        loop {
+            match self.rest().first().copied() {
+                Some(c) if c == b',' || c.is_ascii_whitespace() => self.consume(1),
+                Some(_) => return Ok(()),
+                None if self.fill()? => continue,
+                None => return Ok(()),
+            }
+        }
+    }
+
+    /// finds the end of the json value starting at the current byte, reading as much as needed.
+    fn scan(&mut self) -> Result<Scan> {
+        loop {
+            match scan_value(self.rest()) {
+                Scan::Incomplete if self.fill()? => continue,
+                scan => return Ok(scan),
+            }
+        }
+    }
+}
+
+/// ReadWindow reads the tracefile in blocks on demand.
+struct ReadWindow<R> {
+    source: R,
+    /// offset of the first byte of `buf` in the tracefile.
+    offset: u64,
+    buf: Vec<u8>,
+    /// position of the current byte in `buf`.
+    pos: usize,
+}
+
+impl<R: Read + Seek> ReadWindow<R> {
+    fn new(mut source: R, offset: u64) -> Result<Self> {
+        source.seek(SeekFrom::Start(offset))?;
+        Ok(Self {
+            source,
+            offset,
+            buf: Vec::new(),
+            pos: 0,
+        })
+    }
+}
+
+impl<R: Read + Seek> Window for ReadWindow<R> {
+    fn position(&self) -> u64 {
+        self.offset + self.pos as u64
+    }
+
+    fn rest(&self) -> &[u8] {
+        &self.buf[self.pos..]
+    }
+
+    fn consume(&mut self, nbytes: usize) {
+        self.pos += nbytes;
+    }
+
+    /// reads another block of the tracefile, dropping the bytes already consumed.
+    fn fill(&mut self) -> Result<bool> {
+        self.buf.drain(..self.pos);
+        self.offset += self.pos as u64;
+        self.pos = 0;
+        let nbytes = self
+            .source
+            .by_ref()
+            .take(BLOCK_SIZE)
+            .read_to_end(&mut self.buf)?;
+        Ok(nbytes > 0)
+    }
+}
+
+/// SliceWindow walks over a tracefile that is entirely in memory.
+struct SliceWindow<'a> {
+    data: &'a [u8],
+    /// position of the current byte in `data`.
+    pos: usize,
+}
+
+impl<'a> SliceWindow<'a> {
+    fn new(data: &'a [u8], offset: u64) -> Self {
+        Self {
+            data,
+            pos: (offset as usize).min(data.len()),
+        }
+    }
+}
+
+impl Window for SliceWindow<'_> {
+    fn position(&self) -> u64 {
+        self.pos as u64
+    }
+
+    fn rest(&self) -> &[u8] {
+        &self.data[self.pos..]
+    }
+
+    fn consume(&mut self, nbytes: usize) {
+        self.pos += nbytes;
+    }
+
+    fn fill(&mut self) -> Result<bool> {
+        Ok(false)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::io::Cursor;
+
+    use super::{align_chunks, scan_value, split_chunk, split_slice, ChunkBounds, Scan};
+    use crate::{format::parse_trace, Event};
+
+    /// splits a trace into the given number of chunks the way the parallel readers do, either
+    /// reading it or walking over it in memory.
+    fn split_trace(data: &[u8], num_chunks: usize, in_memory: bool) -> (Vec<Event>, bool) {
+        let init_skip = crate::locate_events(data).unwrap().offset;
+        let chunk_size = (data.len() as u64 - init_skip)
+            .div_ceil(num_chunks as u64)
+            .max(1);
+        let read = |start: u64, end: u64, aligned: bool| {
+            let mut events = Vec::new();
+            let on_event =
+                |event: &[u8]| events.extend(serde_json::from_slice::<Event>(event).ok());
+            let bounds = match in_memory {
+                true => split_slice(data, start, end, aligned, on_event)?,
+                false => split_chunk(Cursor::new(data), start, end, aligned, on_event)?,
+            };
+            Ok((events, bounds))
+        };
+
+        let mut chunks: Vec<(Vec<Event>, ChunkBounds)> = (0..num_chunks as u64)
+            .map(|i| {
+                let start = init_skip + i * chunk_size;
+                read(start, start + chunk_size, i == 0).unwrap()
+            })
+            .collect();
+        align_chunks(&mut chunks, |start, end| read(start, end, true)).unwrap();
+
+        let closed = chunks.last().is_some_and(|(_, bounds)| bounds.closed);
+        let events = chunks.into_iter().flat_map(|(events, _)| events).collect();
+        (events, closed)
+    }
+
+    #[test]
+    fn check_values_are_scanned() {
+        assert_eq!(scan_value(br#"{"a":"}]\"{"},"#), Scan::Complete(13));
+        assert_eq!(scan_value(br#"{"a":[1,{"b":"\\"}]}]"#), Scan::Complete(20));
+        assert_eq!(scan_value(b"12.5e3,"), Scan::Complete(6));
+        assert_eq!(scan_value(br#""a\"b" "#), Scan::Complete(6));
+        assert_eq!(scan_value(br#"{"a":"\"}"#), Scan::Incomplete);
+        assert_eq!(scan_value(b"],"), Scan::Invalid);
+    }
+
+    #[test]
+    fn check_any_layout_is_split_into_every_event() {
+        for file_path in [
+            "../data/trace-valid-ending.json",
+            "../data/trace-array.json",
+            "../data/trace-minified.json",
+            "../data/trace-pretty.json",
+            "../data/trace-crlf.json",
+            "../data/trace-lenient.json",
+            "../data/trace-truncated.json",
+        ] {
+            let data = std::fs::read(file_path).unwrap();
+            let trace = parse_trace(&data).unwrap();
+            for (num_chunks, in_memory) in (1..=64).flat_map(|n| [(n, false), (n, true)]) {
+                let (events, closed) = split_trace(&data, num_chunks, in_memory);
+                assert_eq!(trace.events, events, "{file_path} in {num_chunks} chunks");
+                assert_eq!(
+                    trace.truncated, !closed,
+                    "{file_path} in {num_chunks} chunks"
+                );
+            }
+        }
+    }
+}
@@ -1,41 +1,179 @@
-use serde::Deserialize;
+use serde::{
+    de::{self, Visitor},
+    Deserialize, Deserializer,
+};
 
 /// Trace represents the data in a tracefile
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Deserialize, Default)]
 pub struct Trace {
     #[serde(rename = "traceEvents")]
     pub events: Vec<Event>,
+
+    /// raw ftrace text recorded alongside the events, e.g. by systrace or chrome's tracing on
+    /// android and linux.
+    #[serde(rename = "systemTraceEvents")]
+    pub system_trace_events: Option<String>,
+
+    /// the rest of the fields of the trace object.
+    #[serde(skip)]
+    pub fields: TraceFields,
+
+    /// denotes whether the tracefile ended before its event array was closed.
+    #[serde(skip)]
+    pub truncated: bool,
 }
 
-pub type ProcessId = i32;
-pub type ThreadId = i32;
+/// TraceFields are the fields of the trace object besides its events, which describe the
+/// capture, e.g. its date or the version and command line of the browser, and hold the tables
+/// that the events refer to, e.g. the stack frames.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct TraceFields {
+    /// the unit the timestamps are preferred to be displayed in, i.e., `ms` or `ns`.
+    pub display_time_unit: Option<String>,
+    pub metadata: Option<serde_json::Value>,
+    pub other_data: Option<serde_json::Value>,
+    pub stack_frames: Option<serde_json::Value>,
+    pub samples: Option<serde_json::Value>,
+    pub controller_trace_data_key: Option<String>,
+    /// any other field, by its key.
+    pub extra: serde_json::Map<String, serde_json::Value>,
+}
+
+impl TraceFields {
+    /// looks a key up in `metadata`, or else in `otherData` where older traces keep it.
+    pub fn lookup(&self, key: &str) -> Option<&serde_json::Value> {
+        [&self.metadata, &self.other_data]
+            .into_iter()
+            .flatten()
+            .find_map(|fields| fields.get(key))
+    }
+
+    /// the date and time the trace was captured at.
+    pub fn capture_date(&self) -> Option<&str> {
+        self.lookup("trace-capture-datetime")?.as_str()
+    }
+
+    /// the version of the traced browser, e.g. `Chrome/120.0.6099.109`.
+    pub fn product_version(&self) -> Option<&str> {
+        self.lookup("product-version")
+            .or_else(|| self.lookup("version"))?
+            .as_str()
+    }
+
+    /// the command line the traced browser was started with.
+    pub fn command_line(&self) -> Option<&str> {
+        self.lookup("command_line")?.as_str()
+    }
+
+    /// moves the fields of another TraceFields into this one, keeping the ones already set.
+    pub fn merge(&mut self, other: Self) {
+        fn keep<T>(field: &mut Option<T>, other: Option<T>) {
+            if field.is_none() {
+                *field = other;
+            }
+        }
+        keep(&mut self.display_time_unit, other.display_time_unit);
+        keep(&mut self.metadata, other.metadata);
+        keep(&mut self.other_data, other.other_data);
+        keep(&mut self.stack_frames, other.stack_frames);
+        keep(&mut self.samples, other.samples);
+        keep(
+            &mut self.controller_trace_data_key,
+            other.controller_trace_data_key,
+        );
+        for (key, value) in other.extra {
+            self.extra.entry(key).or_insert(value);
+        }
+    }
+}
+
+pub type ProcessId = i64;
+pub type ThreadId = i64;
 pub type Scope = String;
-pub type Id = usize;
 pub type Category = String;
+/// Timestamp is a point in time, or a span of it, in nanoseconds.
+/// the tracefile gives them in microseconds, possibly with a fractional part.
+pub type Timestamp = i64;
 
 /// Event represent each event in a tracefile
+/// the schema is lenient, i.e., missing fields get their defaults and fields of the wrong type
+/// are coerced. see `schema` for the strict checks.
 #[derive(Debug, Deserialize, Clone, Eq, PartialEq, Default)]
 pub struct Event {
+    #[serde(default)]
+    #[serde(deserialize_with = "utils::de_lenient_string")]
     pub name: String,
 
     #[serde(rename = "cat")]
+    #[serde(default)]
+    #[serde(deserialize_with = "utils::de_lenient_string")]
     pub category: Category,
+    /// the id of an async event or an object, given by `id`. see [`Event::id`] for the id that
+    /// the event goes by.
     #[serde(default)]
-    #[serde(deserialize_with = "utils::de_hex_to_int")]
     pub id: Id,
+    /// the id given by `id2`, which takes priority over `id` if an event has both.
+    #[serde(default)]
+    pub id2: Id,
     #[serde(default)]
+    #[serde(deserialize_with = "utils::de_lenient_string")]
     pub scope: Scope,
 
     #[serde(rename = "ph")]
+    #[serde(default = "EventPhase::missing")]
     pub phase_type: EventPhase,
+    /// the scope of an instant event, given by its `s` field.
+    #[serde(rename = "s")]
+    #[serde(default)]
+    pub instant_scope: InstantScope,
 
+    #[serde(default)]
+    #[serde(deserialize_with = "utils::de_lenient_int")]
     pub pid: ProcessId,
+    #[serde(default)]
+    #[serde(deserialize_with = "utils::de_lenient_int")]
     pub tid: ThreadId,
 
     #[serde(rename = "ts")]
-    pub timestamp: i64,
+    #[serde(default)]
+    #[serde(deserialize_with = "utils::de_micros_to_nanos")]
+    pub timestamp: Timestamp,
     #[serde(rename = "dur")]
-    pub duration: Option<i64>,
+    #[serde(default)]
+    #[serde(deserialize_with = "utils::de_opt_micros_to_nanos")]
+    pub duration: Option<Timestamp>,
+
+    /// the time of the thread's clock, which only advances while the thread runs on a cpu.
+    #[serde(rename = "tts")]
+    #[serde(default)]
+    #[serde(deserialize_with = "utils::de_opt_micros_to_nanos")]
+    pub thread_timestamp: Option<Timestamp>,
+    #[serde(rename = "tdur")]
+    #[serde(default)]
+    #[serde(deserialize_with = "utils::de_opt_micros_to_nanos")]
+    pub thread_duration: Option<Timestamp>,
+
+    /// the binding point of a flow event, where `e` binds it to the enclosing slice.
+    #[serde(rename = "bp")]
+    #[serde(default)]
+    pub binding_point: Option<String>,
+    /// the flow that a slice is bound to, going into and or out of the slice.
+    #[serde(default)]
+    pub bind_id: Id,
+    #[serde(default)]
+    pub flow_in: bool,
+    #[serde(default)]
+    pub flow_out: bool,
+
+    /// the stack of a sample, or of a slice, as the id of its leaf frame in `stackFrames`.
+    #[serde(rename = "sf")]
+    #[serde(default)]
+    #[serde(deserialize_with = "utils::de_lenient_opt_string")]
+    pub stack_frame: Option<String>,
+    /// the stack given inline rather than by `sf`, e.g. as program counters, from the root frame.
+    #[serde(default)]
+    #[serde(deserialize_with = "utils::de_lenient_strings")]
+    pub stack: Vec<String>,
 
     pub args: Option<serde_json::Value>,
 }
@@ -49,8 +187,11 @@ impl Event {
         if !other.category.is_empty() {
             std::mem::swap(&mut self.category, &mut other.category);
         }
-        if other.id != 0 {
-            self.id = other.id
+        if other.id != Id::None {
+            std::mem::swap(&mut self.id, &mut other.id);
+        }
+        if other.id2 != Id::None {
+            std::mem::swap(&mut self.id2, &mut other.id2);
         }
         if !other.scope.is_empty() {
             std::mem::swap(&mut self.scope, &mut other.scope);
@@ -67,6 +208,25 @@ impl Event {
     }
 }
 
+impl Event {
+    /// the id the event goes by, i.e., its `id2` if it has one or else its `id`.
+    pub fn id(&self) -> &Id {
+        match self.id2 {
+            Id::None => &self.id,
+            _ => &self.id2,
+        }
+    }
+
+    /// the pid that qualifies the id of the event, which is only set if the id is local to the
+    /// process.
+    pub fn id_pid(&self) -> Option<ProcessId> {
+        match self.id() {
+            Id::Local(_) => Some(self.pid),
+            _ => None,
+        }
+    }
+}
+
 impl Ord for Event {
     fn cmp(&self, other: &Self) -> std::cmp::Ordering {
         self.timestamp.cmp(&other.timestamp)
@@ -75,7 +235,7 @@ impl Ord for Event {
 
 impl PartialOrd for Event {
     fn partial_cmp(&self, other: &Self) -> Option<std::cmp::Ordering> {
-        self.timestamp.partial_cmp(&other.timestamp)
+        Some(self.cmp(other))
     }
 }
 
@@ -84,65 +244,296 @@ impl std::fmt::Display for Event {
         write!(
             f,
             "[{}]:{}<s: {},id: {},cat: {}| pid: {},tid: {}>",
-            self.phase_type, self.timestamp, self.scope, self.id, self.category, self.pid, self.tid
+            self.phase_type,
+            self.timestamp,
+            self.scope,
+            self.id(),
+            self.category,
+            self.pid,
+            self.tid
         )
     }
 }
 
-#[derive(Debug, Deserialize, Clone, Eq, PartialEq, Hash, Default)]
+/// Id identifies an async operation or an object.
+/// global ids are shared by every process while local ids, i.e. `"id2": {"local": ...}`, are only
+/// unique within the process that emits them.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, PartialOrd, Ord, Default)]
+pub enum Id {
+    #[default]
+    None,
+    Global(IdValue),
+    Local(IdValue),
+}
+
+/// IdValue is the value of an id, which is usually an integer, possibly written as a hex
+/// string, but might be any string such as `"request-42"`.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub enum IdValue {
+    Int(u64),
+    Str(String),
+}
+
+impl IdValue {
+    /// parses an id given as a string, which is taken as a hex integer if it is one.
+    pub fn parse(v: &str) -> Self {
+        let hex = v
+            .strip_prefix("0x")
+            .or_else(|| v.strip_prefix("0X"))
+            .unwrap_or(v);
+        match u64::from_str_radix(hex, 16) {
+            Ok(num) => IdValue::Int(num),
+            Err(_) => IdValue::Str(v.into()),
+        }
+    }
+}
+
+impl std::fmt::Display for Id {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Id::None => Ok(()),
+            Id::Global(value) => write!(f, "{value}"),
+            Id::Local(value) => write!(f, "local:{value}"),
+        }
+    }
+}
+
+impl std::fmt::Display for IdValue {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            IdValue::Int(num) => write!(f, "{num:#x}"),
+            IdValue::Str(v) => write!(f, "{v}"),
+        }
+    }
+}
+
+impl<'de> Deserialize<'de> for Id {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        struct IdVisitor;
+
+        impl<'de> Visitor<'de> for IdVisitor {
+            type Value = Id;
+
+            fn expecting(&self, formatter: &mut std::fmt::Formatter) -> std::fmt::Result {
+                formatter.write_str("an id, or an object of a local or a global id")
+            }
+
+            fn visit_unit<E>(self) -> Result<Self::Value, E>
+            where
+                E: de::Error,
+            {
+                Ok(Id::None)
+            }
+
+            fn visit_i64<E>(self, v: i64) -> Result<Self::Value, E>
+            where
+                E: de::Error,
+            {
+                // a negative id keeps its sign as a string, rather than aliasing a large one
+                Ok(Id::Global(match u64::try_from(v) {
+                    Ok(v) => IdValue::Int(v),
+                    Err(_) => IdValue::Str(v.to_string()),
+                }))
+            }
+
+            fn visit_u64<E>(self, v: u64) -> Result<Self::Value, E>
+            where
+                E: de::Error,
+            {
+                Ok(Id::Global(IdValue::Int(v)))
+            }
+
+            fn visit_f64<E>(self, v: f64) -> Result<Self::Value, E>
+            where
+                E: de::Error,
+            {
+                // only whole ids in the range of an integer id are one, the others are kept as
+                // they are written
+                let whole = v.fract() == 0.0 && v >= 0.0 && v < u64::MAX as f64;
+                Ok(Id::Global(match whole {
+                    true => IdValue::Int(v as u64),
+                    false => IdValue::Str(v.to_string()),
+                }))
+            }
+
+            fn visit_str<E>(self, v: &str) -> Result<Self::Value, E>
+            where
+                E: de::Error,
+            {
+                Ok(Id::Global(IdValue::parse(v)))
+            }
+
+            fn visit_map<A>(self, mut map: A) -> Result<Self::Value, A::Error>
+            where
+                A: de::MapAccess<'de>,
+            {
+                let mut id = Id::None;
+                while let Some(key) = map.next_key::<std::borrow::Cow<str>>()? {
+                    match &*key {
+                        "local" => id = Id::Local(map.next_value()?),
+                        "global" => id = Id::Global(map.next_value()?),
+                        _ => {
+                            map.next_value::<de::IgnoredAny>()?;
+                        }
+                    }
+                }
+                Ok(id)
+            }
+        }
+
+        deserializer.deserialize_any(IdVisitor)
+    }
+}
+
+impl<'de> Deserialize<'de> for IdValue {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        match Id::deserialize(deserializer)? {
+            Id::Global(value) | Id::Local(value) => Ok(value),
+            Id::None => Err(de::Error::custom("missing id")),
+        }
+    }
+}
+
+/// InstantScope tells which part of the application an instant event marks.
+/// thread scoped instants belong to the timeline of their thread, while process and global scoped
+/// ones are markers for every thread of their process, or of the whole trace.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub enum InstantScope {
+    Global,
+    Process,
+    #[default]
+    Thread,
+}
+
+impl<'de> Deserialize<'de> for InstantScope {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        // anything but `g` and `p`, including a missing scope, means the thread
+        Ok(match utils::de_lenient_string(deserializer)?.as_str() {
+            "g" => InstantScope::Global,
+            "p" => InstantScope::Process,
+            _ => InstantScope::Thread,
+        })
+    }
+}
+
+/// EventPhase is the type of an event, given by its `ph` field.
+/// phases that are not known are kept as `Unknown` rather than rejected.
+#[derive(Debug, Clone, Eq, PartialEq, Hash, Default)]
 pub enum EventPhase {
-    #[serde(rename = "B")]
     SyncBegin,
-    #[serde(rename = "E")]
     SyncEnd,
-    #[serde(rename = "i", alias = "I")]
     SyncInstant,
 
-    #[serde(rename = "b")]
     AsyncBegin,
-    #[serde(rename = "e")]
     AsyncEnd,
-    #[serde(rename = "n")]
     AsyncInstant,
 
-    #[serde(rename = "s")]
+    /// the deprecated async phases, which are still emitted by older tracers.
+    AsyncStart,
+    AsyncStepInto,
+    AsyncStepPast,
+    AsyncFinish,
+
     FlowStart,
-    #[serde(rename = "f")]
     FlowEnd,
-    #[serde(rename = "t")]
     FlowStep,
 
-    #[serde(rename = "N")]
     ObjectCreate,
-    #[serde(rename = "D")]
     ObjectDestroy,
-    #[serde(rename = "O")]
     ObjectSnapshot,
 
-    #[serde(rename = "V")]
     MemoryDumpGlobal,
-    #[serde(rename = "v")]
     MemoryDumpProcess,
 
-    #[serde(rename = "(")]
     ContextEnter,
-    #[serde(rename = ")")]
     ContextLeave,
 
-    #[serde(rename = "M")]
     Metadata,
-    #[serde(rename = "R")]
     Mark,
-    #[serde(rename = "c")]
     Clock,
-    #[serde(rename = "P")]
     #[default]
     Sample,
-    #[serde(rename = "X")]
     Complete,
-    #[serde(rename = "C")]
     Counter,
+
+    Unknown(String),
+}
+
+impl EventPhase {
+    /// the phase of an event without a `ph` field.
+    fn missing() -> Self {
+        EventPhase::Unknown(String::new())
+    }
+
+    /// parses the `ph` field of an event.
+    pub fn from_code(code: &str) -> Self {
+        match code {
+            "B" => EventPhase::SyncBegin,
+            "E" => EventPhase::SyncEnd,
+            "i" | "I" => EventPhase::SyncInstant,
+            "b" => EventPhase::AsyncBegin,
+            "e" => EventPhase::AsyncEnd,
+            "n" => EventPhase::AsyncInstant,
+            "S" => EventPhase::AsyncStart,
+            "T" => EventPhase::AsyncStepInto,
+            "p" => EventPhase::AsyncStepPast,
+            "F" => EventPhase::AsyncFinish,
+            "s" => EventPhase::FlowStart,
+            "f" => EventPhase::FlowEnd,
+            "t" => EventPhase::FlowStep,
+            "N" => EventPhase::ObjectCreate,
+            "D" => EventPhase::ObjectDestroy,
+            "O" => EventPhase::ObjectSnapshot,
+            "V" => EventPhase::MemoryDumpGlobal,
+            "v" => EventPhase::MemoryDumpProcess,
+            "(" => EventPhase::ContextEnter,
+            ")" => EventPhase::ContextLeave,
+            "M" => EventPhase::Metadata,
+            "R" => EventPhase::Mark,
+            "c" => EventPhase::Clock,
+            "P" => EventPhase::Sample,
+            "X" => EventPhase::Complete,
+            "C" => EventPhase::Counter,
+            code => EventPhase::Unknown(code.into()),
+        }
+    }
 }
+
+impl<'de> Deserialize<'de> for EventPhase {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        struct PhaseVisitor;
+
+        impl Visitor<'_> for PhaseVisitor {
+            type Value = EventPhase;
+
+            fn expecting(&self, formatter: &mut std::fmt::Formatter) -> std::fmt::Result {
+                formatter.write_str("an event phase")
+            }
+
+            fn visit_str<E>(self, v: &str) -> Result<Self::Value, E>
+            where
+                E: de::Error,
+            {
+                Ok(EventPhase::from_code(v))
+            }
+        }
+
+        deserializer.deserialize_str(PhaseVisitor)
+    }
+}
+
 impl std::fmt::Display for EventPhase {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         let display = match self {
@@ -152,6 +543,10 @@ impl std::fmt::Display for EventPhase {
             EventPhase::AsyncBegin => "b",
             EventPhase::AsyncEnd => "e",
             EventPhase::AsyncInstant => "n",
+            EventPhase::AsyncStart => "S",
+            EventPhase::AsyncStepInto => "T",
+            EventPhase::AsyncStepPast => "p",
+            EventPhase::AsyncFinish => "F",
             EventPhase::FlowStart => "s",
             EventPhase::FlowEnd => "f",
             EventPhase::FlowStep => "t",
@@ -168,6 +563,7 @@ impl std::fmt::Display for EventPhase {
             EventPhase::Sample => "P",
             EventPhase::Complete => "X",
             EventPhase::Counter => "C",
+            EventPhase::Unknown(code) => code,
         };
         write!(f, "{display}")
     }
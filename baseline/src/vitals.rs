@@ -0,0 +1,210 @@
+use std::fmt::Display;
+
+use log::debug;
+use serde_json::Value;
+
+use crate::{CCTNode, Event, EventPhase, Timestamp, CCT};
+
+/// Metric is a milestone of the load of a page, as told by the mark events.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub enum Metric {
+    FirstContentfulPaint,
+    LargestContentfulPaint,
+    DomContentLoaded,
+    Load,
+}
+
+impl Display for Metric {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let name = match self {
+            Metric::FirstContentfulPaint => "FCP",
+            Metric::LargestContentfulPaint => "LCP",
+            Metric::DomContentLoaded => "DOMContentLoaded",
+            Metric::Load => "load",
+        };
+        write!(f, "{name}")
+    }
+}
+
+/// Navigation is a page load of a frame, from its `navigationStart` mark. the metrics are the
+/// times from the start of the navigation to its marks.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Navigation {
+    /// the `navigationId` of the marks, or else the frame they are for.
+    pub id: String,
+    pub frame: Option<String>,
+    pub url: Option<String>,
+    pub main_frame: bool,
+    pub start: Timestamp,
+    pub first_contentful_paint: Option<Timestamp>,
+    /// the time of the last candidate for the largest contentful paint.
+    pub largest_contentful_paint: Option<Timestamp>,
+    pub dom_content_loaded: Option<Timestamp>,
+    pub load: Option<Timestamp>,
+}
+
+impl Navigation {
+    /// the time from the start of the navigation to a metric, if it is reached.
+    pub fn get(&self, metric: Metric) -> Option<Timestamp> {
+        match metric {
+            Metric::FirstContentfulPaint => self.first_contentful_paint,
+            Metric::LargestContentfulPaint => self.largest_contentful_paint,
+            Metric::DomContentLoaded => self.dom_content_loaded,
+            Metric::Load => self.load,
+        }
+    }
+
+    /// the metrics that take longer than their budget, along with their time. a metric that is
+    /// not reached is not over its budget.
+    pub fn over_budget(&self, budget: &[(Metric, Timestamp)]) -> Vec<(Metric, Timestamp)> {
+        budget
+            .iter()
+            .filter_map(|(metric, limit)| {
+                let time = self.get(*metric)?;
+                (time > *limit).then_some((*metric, time))
+            })
+            .collect()
+    }
+}
+
+/// WebVitals is the report of the page loads of a trace, made from its mark events, i.e., the
+/// ones of phase `R`.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct WebVitals {
+    /// the navigations in order of their start.
+    pub navigations: Vec<Navigation>,
+}
+
+impl WebVitals {
+    /// collects the marks of the sync CCTs, which keep them as instants of the threads that emit
+    /// them.
+    pub fn from_sync_tasks<'a>(sync_tasks: impl IntoIterator<Item = &'a CCT>) -> Self {
+        let marks = sync_tasks
+            .into_iter()
+            .flatten()
+            .map(CCTNode::event)
+            .filter(|event| event.phase_type == EventPhase::Mark);
+        Self::from_marks(marks)
+    }
+
+    /// collects the marks of each navigation. a mark belongs to the navigation of its
+    /// `args.data.navigationId`, or else to the last navigation of its `args.frame` that started
+    /// before it.
+    pub fn from_marks<'a>(marks: impl IntoIterator<Item = &'a Event>) -> Self {
+        let mut marks: Vec<&Event> = marks.into_iter().collect();
+        // the marks of different threads at the same time are taken in the same order every time
+        marks.sort_by_key(|mark| (mark.timestamp, mark.pid, mark.tid));
+        let mut navigations: Vec<Navigation> = Vec::new();
+        for mark in marks {
+            let args = mark.args.as_ref();
+            let string = |value: Option<&Value>| value?.as_str().map(String::from);
+            let frame = string(args.and_then(|args| args.get("frame")));
+            let data = args.and_then(|args| args.get("data"));
+            let navigation_id = string(data.and_then(|data| data.get("navigationId")));
+
+            if mark.name == "navigationStart" {
+                let Some(id) = navigation_id.clone().or(frame.clone()) else {
+                    debug!("navigation at {} has no id", mark.timestamp);
+                    continue;
+                };
+                navigations.push(Navigation {
+                    id,
+                    frame,
+                    url: string(data.and_then(|data| data.get("documentLoaderURL"))),
+                    main_frame: data
+                        .and_then(|data| data.get("isLoadingMainFrame"))
+                        .and_then(Value::as_bool)
+                        .unwrap_or_default(),
+                    start: mark.timestamp,
+                    ..Default::default()
+                });
+                continue;
+            }
+
+            let navigation = navigations
+                .iter_mut()
+                .rev()
+                .find(|navigation| match &navigation_id {
+                    Some(id) => navigation.id == *id,
+                    None => frame.is_some() && navigation.frame == frame,
+                });
+            let Some(navigation) = navigation else {
+                debug!("mark {} is not of a navigation", mark.name);
+                continue;
+            };
+            let time = Some(mark.timestamp - navigation.start);
+            match mark.name.as_str() {
+                "firstContentfulPaint" => {
+                    navigation.first_contentful_paint = navigation.first_contentful_paint.or(time)
+                }
+                "largestContentfulPaint::Candidate" => navigation.largest_contentful_paint = time,
+                "largestContentfulPaint::Invalidate" => navigation.largest_contentful_paint = None,
+                "domContentLoadedEventEnd" => {
+                    navigation.dom_content_loaded = navigation.dom_content_loaded.or(time)
+                }
+                "loadEventEnd" => navigation.load = navigation.load.or(time),
+                _ => (),
+            }
+        }
+        WebVitals { navigations }
+    }
+
+    /// the navigations of the main frame, which are the page loads proper.
+    pub fn main_frame(&self) -> impl Iterator<Item = &Navigation> {
+        self.navigations
+            .iter()
+            .filter(|navigation| navigation.main_frame)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::path::Path;
+
+    use super::Metric;
+    use crate::{build_application_cct, collect_traces};
+
+    #[test]
+    fn check_marks_give_the_metrics_of_each_navigation() -> std::io::Result<()> {
+        let trace = collect_traces(Path::new("../data/trace-vitals.json"))?;
+        let app_cct = build_application_cct(trace);
+        let vitals = &app_cct.web_vitals;
+
+        let ids: Vec<_> = vitals.navigations.iter().map(|n| n.id.as_str()).collect();
+        assert_eq!(ids, ["N1", "N2", "N3"]);
+        let main_frame: Vec<_> = vitals.main_frame().map(|n| n.id.as_str()).collect();
+        assert_eq!(main_frame, ["N1", "N3"]);
+
+        let first = &vitals.navigations[0];
+        assert_eq!(first.url.as_deref(), Some("https://example.com/"));
+        assert_eq!(first.first_contentful_paint, Some(500_000));
+        // the last candidate is the largest contentful paint
+        assert_eq!(first.largest_contentful_paint, Some(1_200_000));
+        // marks without a navigation id go to the last navigation of their frame
+        assert_eq!(first.dom_content_loaded, Some(800_000));
+        assert_eq!(first.load, Some(1_500_000));
+        assert_eq!(vitals.navigations[1].first_contentful_paint, Some(200_000));
+
+        // the marks stay in the CCT of their thread, including the ones of no navigation
+        let marks = app_cct.sync_tasks[&(1, 1)].into_iter().skip(1);
+        assert_eq!(marks.clone().count(), 13);
+        assert!(marks
+            .map(|node| node.event())
+            .any(|mark| mark.name == "someMark"));
+
+        let next = &vitals.navigations[2];
+        assert_eq!(next.largest_contentful_paint, None);
+        assert_eq!(next.load, Some(1_000_000));
+
+        let budget = [
+            (Metric::FirstContentfulPaint, 1_000_000),
+            (Metric::LargestContentfulPaint, 1_000_000),
+        ];
+        assert_eq!(
+            first.over_budget(&budget),
+            [(Metric::LargestContentfulPaint, 1_200_000)]
+        );
+        assert!(next.over_budget(&budget).is_empty());
+        Ok(())
+    }
+}
@@ -0,0 +1,169 @@
+use std::{
+    io::{Error, ErrorKind, Result},
+    panic::{catch_unwind, AssertUnwindSafe},
+    path::{Path, PathBuf},
+    time::Duration,
+};
+
+use log::{info, warn};
+use rayon::prelude::*;
+
+/// Summary is what is reported about a trace that is processed successfully.
+#[derive(Debug, Default, Clone)]
+pub struct Summary {
+    pub collect: Duration,
+    pub build: Duration,
+    pub sync_tasks: usize,
+    pub async_tasks: usize,
+    pub object_life_cycle: usize,
+}
+
+/// Report is the outcome of processing a single trace of a batch.
+#[derive(Debug)]
+pub struct Report {
+    pub trace: PathBuf,
+    pub summary: std::result::Result<Summary, String>,
+}
+
+/// finds the tracefiles of a batch, i.e., either every file right inside a directory, or every
+/// file matching a glob pattern such as `traces/**/*.json.gz`.
+pub fn find_traces(pattern: &str) -> Result<Vec<PathBuf>> {
+    let path = Path::new(pattern);
+    let mut traces = Vec::new();
+    if path.is_dir() {
+        for entry in path.read_dir()? {
+            let entry = entry?;
+            if entry.file_type()?.is_file() {
+                traces.push(entry.path());
+            }
+        }
+    } else {
+        let paths = glob::glob(pattern).map_err(|e| Error::new(ErrorKind::InvalidInput, e))?;
+        for path in paths {
+            let path = path.map_err(Error::from)?;
+            if path.is_file() {
+                traces.push(path);
+            }
+        }
+    }
+    traces.sort();
+    Ok(traces)
+}
+
+/// processes the traces concurrently and reports on every one of them, failed or not.
+/// the threads are split between the traces processed at the same time and the processing of
+/// each trace, e.g. 8 threads process 4 traces at a time with 2 threads each if there are 4 or
+/// more traces, but a single trace with all 8.
+pub fn run<F>(traces: &[PathBuf], num_threads: usize, process: F) -> Result<Vec<Report>>
+where
+    F: Fn(&Path) -> Result<Summary> + Sync,
+{
+    let traces_at_once = traces.len().clamp(1, num_threads.max(1));
+    let threads_per_trace = (num_threads / traces_at_once).max(1);
+    info!("processing {traces_at_once} traces at a time with {threads_per_trace} threads each");
+
+    let pool = rayon::ThreadPoolBuilder::new()
+        .num_threads(traces_at_once)
+        .build()
+        .map_err(Error::other)?;
+
+    let reports = pool.install(|| {
+        traces
+            .par_iter()
+            .with_max_len(1)
+            .map(|trace| Report {
+                trace: trace.clone(),
+                summary: process_trace(trace, threads_per_trace, &process),
+            })
+            .collect()
+    });
+    Ok(reports)
+}
+
+/// processes a single trace within its own pool of threads.
+/// a trace that fails, even by panicking, is reported rather than ending the whole batch.
+fn process_trace<F>(
+    trace: &Path,
+    num_threads: usize,
+    process: &F,
+) -> std::result::Result<Summary, String>
+where
+    F: Fn(&Path) -> Result<Summary> + Sync,
+{
+    let pool = rayon::ThreadPoolBuilder::new()
+        .num_threads(num_threads)
+        .build()
+        .map_err(|e| e.to_string())?;
+
+    let summary = match catch_unwind(AssertUnwindSafe(|| pool.install(|| process(trace)))) {
+        Ok(Ok(summary)) => Ok(summary),
+        Ok(Err(e)) => Err(e.to_string()),
+        Err(panic) => Err(match panic.downcast_ref::<&str>() {
+            Some(msg) => format!("panicked: {msg}"),
+            None => match panic.downcast_ref::<String>() {
+                Some(msg) => format!("panicked: {msg}"),
+                None => "panicked".into(),
+            },
+        }),
+    };
+    if let Err(e) = &summary {
+        warn!("failed to process {}: {e}", trace.to_string_lossy());
+    }
+    summary
+}
+
+/// prints a row per trace, followed by how many of them failed.
+pub fn print_reports(reports: &[Report]) {
+    println!("trace\tstatus\tcollect (ms)\tbuild (ms)\tsync tasks\tasync tasks\tobjects");
+    for report in reports {
+        let trace = report.trace.to_string_lossy();
+        match &report.summary {
+            Ok(summary) => println!(
+                "{trace}\tok\t{}\t{}\t{}\t{}\t{}",
+                summary.collect.as_millis(),
+                summary.build.as_millis(),
+                summary.sync_tasks,
+                summary.async_tasks,
+                summary.object_life_cycle
+            ),
+            Err(e) => println!("{trace}\terror: {e}\t\t\t\t\t"),
+        }
+    }
+    let failed = reports.iter().filter(|r| r.summary.is_err()).count();
+    println!("{} traces, {failed} failed", reports.len());
+}
+
+#[cfg(test)]
+mod test {
+    use std::{io::ErrorKind, path::Path};
+
+    use super::{find_traces, run, Summary};
+
+    #[test]
+    fn check_failures_are_reported_per_trace() -> std::io::Result<()> {
+        let traces = find_traces("../data/trace-*.json")?;
+        assert!(traces.contains(&Path::new("../data/trace-valid-ending.json").to_path_buf()));
+        assert!(!traces.iter().any(|trace| trace.ends_with("trace.ndjson")));
+
+        let reports = run(&traces, 4, |trace| {
+            if trace.ends_with("trace-truncated.json") {
+                Err(std::io::Error::new(ErrorKind::InvalidData, "bad trace"))
+            } else if trace.ends_with("trace-array.json") {
+                panic!("bad array")
+            } else {
+                Ok(Summary::default())
+            }
+        })?;
+        assert_eq!(reports.len(), traces.len());
+        for report in reports {
+            match report.trace.file_name().unwrap().to_str().unwrap() {
+                "trace-truncated.json" => assert_eq!(report.summary.unwrap_err(), "bad trace"),
+                "trace-array.json" => {
+                    assert_eq!(report.summary.unwrap_err(), "panicked: bad array")
+                }
+                _ => assert!(report.summary.is_ok()),
+            }
+        }
+        Ok(())
+    }
+}
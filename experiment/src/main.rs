@@ -1,8 +1,10 @@
 use log::info;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 
 use clap::{Parser, ValueEnum};
 
+mod batch;
+
 #[derive(Clone, ValueEnum, Default, PartialEq)]
 pub enum Mode {
     /// Baseline is the sequential mode in which data is read sequentially, parsed
@@ -23,6 +25,19 @@ pub enum Mode {
     /// ParallelCCT uses ParallelRead for reading from file and uses parallelism in CCT construction
     /// precedure in which, each CCT is handled by a different thread.
     ParallelCCT,
+
+    /// Perfetto reads perfetto's protobuf tracefiles instead of json ones. track events are
+    /// converted into the events of the json format and the CCTs are formed sequentially.
+    Perfetto,
+
+    /// Ndjson reads newline delimited tracefiles, i.e., one event per line with no surrounding
+    /// array, using the same chunked parallel reading as ParallelRead. since every line is a
+    /// complete event, each chunk is read independently.
+    Ndjson,
+
+    /// ParallelMmap maps the file into memory once and parses the events of each chunk right out
+    /// of the mapping concurrently, skipping the copies that ParallelRead makes while reading.
+    ParallelMmap,
 }
 
 #[derive(Parser)]
@@ -30,9 +45,20 @@ pub enum Mode {
 #[command(propagate_version = true)]
 #[clap(rename_all = "kebab_case")]
 struct Opts {
-    /// Address of the trace file
-    #[arg(short, long)]
-    trace: PathBuf,
+    /// Address of the trace file, either plain or gzip/zstd compressed, or `-` to read the trace
+    /// from stdin
+    #[arg(short, long, required_unless_present = "batch")]
+    trace: Option<PathBuf>,
+
+    /// A directory or a glob pattern of trace files to process together, printing a summary row
+    /// per trace
+    #[arg(short, long, conflicts_with = "trace")]
+    batch: Option<String>,
+
+    /// Check the trace against the strict schema of the trace event format before running, and
+    /// fail listing every deviation if there is any
+    #[arg(long, conflicts_with = "batch")]
+    strict: bool,
 
     /// which implementation to run
     #[arg(short, long)]
@@ -52,15 +78,54 @@ fn main() -> std::io::Result<()> {
         .build_global()
         .unwrap();
 
-    info!("trace file: {}", opts.trace.to_string_lossy());
+    if let Some(pattern) = opts.batch {
+        return run_batch(&pattern, opts.mode, opts.num_threads);
+    }
+
+    let trace = opts.trace.unwrap_or_default();
+    match trace == Path::new("-") {
+        true => info!("trace file: stdin"),
+        false => info!("trace file: {}", trace.to_string_lossy()),
+    }
+    if opts.strict {
+        if trace == Path::new("-") {
+            return Err(std::io::Error::new(
+                std::io::ErrorKind::InvalidInput,
+                "the strict check needs a trace file rather than stdin",
+            ));
+        }
+        baseline::collect_traces_strict(&trace)?;
+    }
     match opts.mode {
-        Mode::Baseline => run_baseline(opts.trace),
-        Mode::ParallelRead => run_parallel_read(opts.trace),
-        Mode::ParallelParse => run_parallel_parse(opts.trace),
-        Mode::ParallelCCT => run_parallel_cct(opts.trace),
+        Mode::Baseline => run_baseline(trace),
+        Mode::ParallelRead => run_parallel_read(trace),
+        Mode::ParallelParse => run_parallel_parse(trace),
+        Mode::ParallelCCT => run_parallel_cct(trace),
+        Mode::Perfetto => run_perfetto(trace),
+        Mode::Ndjson => run_ndjson(trace),
+        Mode::ParallelMmap => run_parallel_mmap(trace),
     }
 }
 
+/// processes every trace of a batch with the given mode and prints a summary of them.
+fn run_batch(pattern: &str, mode: Mode, num_threads: usize) -> std::io::Result<()> {
+    let traces = batch::find_traces(pattern)?;
+    info!("batch of {} trace files: {pattern}", traces.len());
+
+    let summarize = match mode {
+        Mode::Baseline => summarize_baseline,
+        Mode::ParallelRead => summarize_parallel_read,
+        Mode::ParallelParse => summarize_parallel_parse,
+        Mode::ParallelCCT => summarize_parallel_cct,
+        Mode::Perfetto => summarize_perfetto,
+        Mode::Ndjson => summarize_ndjson,
+        Mode::ParallelMmap => summarize_parallel_mmap,
+    };
+    let reports = batch::run(&traces, num_threads, summarize)?;
+    batch::print_reports(&reports);
+    Ok(())
+}
+
 fn consume<T>(data: T) {
     _ = data;
 }
@@ -91,19 +156,56 @@ macro_rules! track {
     }};
 }
 
-/// gen_bench! creates a funcion for benchmarking an implementation crate
+/// gen_bench! creates a funcion for benchmarking an implementation crate, along with one that
+/// summarizes a trace of a batch
+/// the reading and building functions default to the crate's `collect_traces`,
+/// `collect_traces_from_stream` and `build_application_cct` but can be given explicitly.
+/// the trace is read from stdin if its address is `-`.
 macro_rules! gen_bench {
     ($crate_name:ident) => {
+        gen_bench!(
+            $crate_name,
+            $crate_name::collect_traces,
+            $crate_name::collect_traces_from_stream,
+            $crate_name::build_application_cct
+        );
+    };
+    ($name:ident, $collect_traces:path, $collect_traces_from_stream:path, $build_application_cct:path) => {
         paste::item! {
-            fn [<run_ $crate_name>](trace: std::path::PathBuf) -> std::io::Result<()> {
+            fn [<run_ $name>](trace: std::path::PathBuf) -> std::io::Result<()> {
                 use std::path::Path;
-                use $crate_name::{build_application_cct, collect_traces};
-
-                let trace = track!(collect_traces(Path::new(&trace)))?;
+                use $collect_traces as collect_traces;
+                use $collect_traces_from_stream as collect_traces_from_stream;
+                use $build_application_cct as build_application_cct;
+
+                let trace = match trace == Path::new("-") {
+                    true => track!(collect_traces_from_stream(std::io::stdin().lock()))?,
+                    false => track!(collect_traces(Path::new(&trace)))?,
+                };
                 let app_cct = track!(build_application_cct(trace));
                 consume(app_cct);
                 Ok(())
             }
+
+            fn [<summarize_ $name>](trace: &std::path::Path) -> std::io::Result<batch::Summary> {
+                use std::time::Instant;
+                use $collect_traces as collect_traces;
+                use $build_application_cct as build_application_cct;
+
+                let start = Instant::now();
+                let trace = collect_traces(trace)?;
+                let collect = start.elapsed();
+
+                let start = Instant::now();
+                let app_cct = build_application_cct(trace);
+                Ok(batch::Summary {
+                    collect,
+                    build: start.elapsed(),
+                    sync_tasks: app_cct.sync_tasks.len(),
+                    async_tasks: app_cct.async_tasks.len(),
+                    object_life_cycle: app_cct.object_life_cycle.len(),
+                })
+            }
         }
     };
 }
@@ -112,3 +214,11 @@ gen_bench!(baseline);
 gen_bench!(parallel_read);
 gen_bench!(parallel_parse);
 gen_bench!(parallel_cct);
+gen_bench!(perfetto);
+gen_bench!(parallel_mmap);
+gen_bench!(
+    ndjson,
+    parallel_read::collect_ndjson_traces,
+    parallel_read::collect_ndjson_traces_from_stream,
+    parallel_read::build_application_cct
+);
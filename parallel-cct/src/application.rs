@@ -1,11 +1,15 @@
 use std::collections::HashMap;
 
-use baseline::{ApplicationCCT, Category, Event, Id, ProcessId, Scope, ThreadId, CCT};
-use rayon::iter::{IntoParallelIterator, ParallelIterator};
+use baseline::{
+    ApplicationCCT, Category, ClockSync, Counters, Event, FlowGraph, Id, Markers, MemoryTimeline,
+    Metadata, ProcessId, Profiles, Scope, ThreadId, TraceFields, WebVitals, CCT,
+};
+use rayon::iter::{IntoParallelIterator, IntoParallelRefMutIterator, ParallelIterator};
 
 pub type SyncTaskId = (ProcessId, ThreadId);
-type AsyncTaskId = (Scope, Id, Category);
-type ObjectLifeCycleId = (Scope, Id);
+/// local ids are qualified by the pid of their process.
+type AsyncTaskId = (Scope, Option<ProcessId>, Id, Category);
+type ObjectLifeCycleId = (Scope, Option<ProcessId>, Id);
 
 /// ApplicationTrace is a middle stage that holds a series of vectors of events,
 /// each later used to construct a new CCT.
@@ -14,6 +18,21 @@ pub struct ApplicationTrace {
     pub sync_tasks: HashMap<SyncTaskId, Vec<Event>>,
     pub async_tasks: HashMap<AsyncTaskId, Vec<Event>>,
     pub object_life_cycle: HashMap<ObjectLifeCycleId, Vec<Event>>,
+    pub markers: Markers,
+    pub metadata: Metadata,
+    pub counters: Counters,
+    /// the sample events, which are resolved along with the `samples` of the fields.
+    pub samples: Vec<Event>,
+    pub memory: MemoryTimeline,
+    /// the clock sync events, which align the clocks of the processes before the CCTs are built.
+    pub clock_syncs: Vec<Event>,
+    /// the flow events, which are resolved once every sync CCT is built.
+    pub flows: Vec<Event>,
+    /// the fields of the trace object besides its events.
+    pub fields: TraceFields,
+
+    /// denotes whether the tracefile ended before its event array was closed.
+    pub truncated: bool,
 }
 
 impl ApplicationTrace {
@@ -21,7 +40,72 @@ impl ApplicationTrace {
         Default::default()
     }
 
-    pub fn application_cct(self) -> ApplicationCCT {
+    /// moves the events of another ApplicationTrace into this one.
+    /// the events of each task are appended after the ones already in this trace.
+    pub fn append(&mut self, other: Self) {
+        for (id, mut events) in other.sync_tasks.into_iter() {
+            self.sync_tasks.entry(id).or_default().append(&mut events)
+        }
+        for (id, mut events) in other.async_tasks.into_iter() {
+            self.async_tasks.entry(id).or_default().append(&mut events)
+        }
+        for (id, mut events) in other.object_life_cycle.into_iter() {
+            self.object_life_cycle
+                .entry(id)
+                .or_default()
+                .append(&mut events)
+        }
+        self.markers.append(other.markers);
+        self.metadata.append(other.metadata);
+        self.counters.append(other.counters);
+        self.samples.extend(other.samples);
+        self.memory.append(other.memory);
+        self.clock_syncs.extend(other.clock_syncs);
+        self.flows.extend(other.flows);
+        self.fields.merge(other.fields);
+    }
+
+    /// puts the events of every task in order of time, keeping the order of the events at the
+    /// same time.
+    pub fn sort(&mut self) {
+        self.sync_tasks
+            .par_iter_mut()
+            .for_each(|(_, events)| events.sort());
+        self.async_tasks
+            .par_iter_mut()
+            .for_each(|(_, events)| events.sort());
+        self.object_life_cycle
+            .par_iter_mut()
+            .for_each(|(_, events)| events.sort());
+    }
+
+    /// moves the timestamps of every process onto the clock of the reference, putting the
+    /// events back in order of time.
+    fn align_clocks(&mut self) -> ClockSync {
+        let clock_sync = ClockSync::from_events(&self.clock_syncs);
+        if clock_sync.is_empty() {
+            return clock_sync;
+        }
+        let events = self
+            .sync_tasks
+            .values_mut()
+            .chain(self.async_tasks.values_mut())
+            .chain(self.object_life_cycle.values_mut())
+            .chain([&mut self.flows, &mut self.samples])
+            .flatten();
+        for event in events {
+            clock_sync.rebase(event);
+        }
+        self.markers.rebase(&clock_sync);
+        self.counters.rebase(&clock_sync);
+        self.memory.rebase(&clock_sync);
+        self.sort();
+        clock_sync
+    }
+
+    pub fn application_cct(mut self) -> ApplicationCCT {
+        let clock_sync = self.align_clocks();
+
         let sync_tasks: HashMap<SyncTaskId, CCT> = self
             .sync_tasks
             .into_par_iter()
@@ -40,10 +124,36 @@ impl ApplicationTrace {
             .map(|(id, events)| (id, CCT::from(events)))
             .collect();
 
-        ApplicationCCT {
+        let mut markers = self.markers;
+        markers.sort();
+        let mut counters = self.counters;
+        counters.sort();
+        let mut memory = self.memory;
+        memory.sort();
+
+        let flows = FlowGraph::resolve(self.flows, &sync_tasks);
+        let web_vitals = WebVitals::from_sync_tasks(sync_tasks.values());
+
+        let mut app_cct = ApplicationCCT {
             sync_tasks,
             async_tasks,
             object_life_cycle,
-        }
+            markers,
+            metadata: self.metadata,
+            counters,
+            profiles: Default::default(),
+            memory,
+            clock_sync,
+            web_vitals,
+            flows,
+            fields: self.fields,
+        };
+        app_cct.name_sync_tasks();
+        let threads = app_cct.threads();
+        app_cct
+            .clock_sync
+            .rebase_samples(&mut app_cct.fields, &threads);
+        app_cct.profiles = Profiles::resolve(self.samples, &app_cct.fields, &threads);
+        app_cct
     }
 }
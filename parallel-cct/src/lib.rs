@@ -1,15 +1,20 @@
 use std::{
     fs::File,
-    io::{BufRead, BufReader, Result},
+    io::{Cursor, Read, Result, Seek, SeekFrom},
     path::Path,
+    sync::Mutex,
 };
 
 use application::ApplicationTrace;
-use baseline::{ApplicationCCT, Event, EventPhase};
-use log::debug;
-use rayon::iter::{IntoParallelRefIterator, ParallelIterator};
+use baseline::{
+    align_chunks, locate_events, read_surroundings, ApplicationCCT, Event, EventPhase, InstantScope,
+};
+use log::{debug, warn};
+use rayon::iter::{IntoParallelIterator, IntoParallelRefIterator, ParallelIterator};
+use utils::{Compression, SharedReader};
 
 mod application;
+mod ndjson;
 mod read;
 
 /// build_application_cct converts ApplicationTrace into ApplicationCCT
@@ -18,47 +23,194 @@ pub fn build_application_cct(app_trace: ApplicationTrace) -> ApplicationCCT {
 }
 
 /// collect_traces reads a tracefile and construct an ApplicationTrace
+/// gzip and zstd compressed tracefiles are decompressed into memory before being split
+/// between threads.
 pub fn collect_traces(file_path: &Path) -> Result<ApplicationTrace> {
+    match Compression::detect(file_path)? {
+        Compression::None => {
+            // read file size
+            let file_size = File::open(file_path)?.metadata()?.len();
+            collect_application_trace(file_size, || File::open(file_path))
+        }
+        _ => {
+            let data = utils::decode_to_vec(File::open(file_path)?)?;
+            collect_application_trace(data.len() as u64, || Ok(Cursor::new(data.as_slice())))
+        }
+    }
+}
+
+/// collect_traces_from_slice construct an ApplicationTrace from a tracefile that is already in
+/// memory
+pub fn collect_traces_from_slice(data: &[u8]) -> Result<ApplicationTrace> {
+    match Compression::from_magic(data) {
+        Compression::None => collect_application_trace(data.len() as u64, || Ok(Cursor::new(data))),
+        _ => collect_traces_from_stream(data),
+    }
+}
+
+/// collect_traces_from_reader construct an ApplicationTrace from the whole content of a seekable
+/// reader. the threads share the reader, each reading its own chunk of it. their reads are
+/// serialized, so only the json parsing runs in parallel, unlike when reading a file by its path.
+pub fn collect_traces_from_reader<R: Read + Seek + Send>(reader: R) -> Result<ApplicationTrace> {
+    let mut reader = reader;
+    match Compression::detect_reader(&mut reader)? {
+        Compression::None => {
+            let trace_size = reader.seek(SeekFrom::End(0))?;
+            let source = Mutex::new(reader);
+            collect_application_trace(trace_size, || Ok(SharedReader::new(&source)))
+        }
+        _ => collect_traces_from_stream(reader),
+    }
+}
+
+/// collect_traces_from_stream construct an ApplicationTrace from a reader that cannot seek,
+/// e.g. stdin
+pub fn collect_traces_from_stream<R: Read>(reader: R) -> Result<ApplicationTrace> {
+    let data = utils::decode_to_vec(reader)?;
+    collect_application_trace(data.len() as u64, || Ok(Cursor::new(data.as_slice())))
+}
+
+/// collect_ndjson_traces reads a newline delimited tracefile, i.e., one event per line,
+/// and construct an ApplicationTrace
+pub fn collect_ndjson_traces(file_path: &Path) -> Result<ApplicationTrace> {
+    match Compression::detect(file_path)? {
+        Compression::None => {
+            let file_size = File::open(file_path)?.metadata()?.len();
+            collect_ndjson_application_trace(file_size, || File::open(file_path))
+        }
+        _ => {
+            let data = utils::decode_to_vec(File::open(file_path)?)?;
+            collect_ndjson_application_trace(data.len() as u64, || Ok(Cursor::new(data.as_slice())))
+        }
+    }
+}
+
+/// collect_ndjson_traces_from_slice construct an ApplicationTrace from a newline delimited
+/// tracefile that is already in memory
+pub fn collect_ndjson_traces_from_slice(data: &[u8]) -> Result<ApplicationTrace> {
+    match Compression::from_magic(data) {
+        Compression::None => {
+            collect_ndjson_application_trace(data.len() as u64, || Ok(Cursor::new(data)))
+        }
+        _ => collect_ndjson_traces_from_stream(data),
+    }
+}
+
+/// collect_ndjson_traces_from_reader construct an ApplicationTrace from the whole content of a
+/// seekable reader holding a newline delimited trace. the threads share the reader, each reading
+/// its own chunk of it. their reads are serialized, so only the json parsing runs in parallel.
+pub fn collect_ndjson_traces_from_reader<R: Read + Seek + Send>(
+    reader: R,
+) -> Result<ApplicationTrace> {
+    let mut reader = reader;
+    match Compression::detect_reader(&mut reader)? {
+        Compression::None => {
+            let trace_size = reader.seek(SeekFrom::End(0))?;
+            let source = Mutex::new(reader);
+            collect_ndjson_application_trace(trace_size, || Ok(SharedReader::new(&source)))
+        }
+        _ => collect_ndjson_traces_from_stream(reader),
+    }
+}
+
+/// collect_ndjson_traces_from_stream construct an ApplicationTrace from a reader that cannot
+/// seek, e.g. stdin, holding a newline delimited trace
+pub fn collect_ndjson_traces_from_stream<R: Read>(reader: R) -> Result<ApplicationTrace> {
+    let data = utils::decode_to_vec(reader)?;
+    collect_ndjson_application_trace(data.len() as u64, || Ok(Cursor::new(data.as_slice())))
+}
+
+/// splits a newline delimited trace between threads and groups each thread's events into an
+/// ApplicationTrace. each thread gets its own seekable handle to the trace by calling `open`.
+fn collect_ndjson_application_trace<R, F>(trace_size: u64, open: F) -> Result<ApplicationTrace>
+where
+    R: Read + Seek,
+    F: Fn() -> Result<R> + Sync,
+{
     let num_threads = rayon::current_num_threads();
     debug!("concurrency level: {num_threads}");
 
-    // read file size
-    let file = File::open(file_path)?;
-    let file_size = file.metadata()?.len();
+    // every line is a complete event, so there is no header to skip
+    let chunk_size = trace_size.div_ceil(num_threads as u64) as usize;
 
-    // ignore the first line since it represents the trace array,
-    // i.e., {"traceEvents":[
-    let init_skip = BufReader::new(file).read_until(b'\n', &mut vec![]).unwrap();
+    let threads: Vec<usize> = (0..num_threads).collect();
+
+    let application_trace = threads
+        .par_iter()
+        .filter_map(|thread_id| ndjson::collect_events(*thread_id, &open, chunk_size).ok())
+        .map(|(events, truncated)| {
+            let mut app_trace = build_application_trace(events);
+            app_trace.truncated = truncated;
+            app_trace
+        })
+        .reduce(Default::default, |mut first, second| {
+            first.truncated |= second.truncated;
+            first.append(second);
+            first
+        });
+
+    Ok(application_trace)
+}
+
+/// splits a trace between threads and groups each thread's events into an ApplicationTrace.
+/// each thread gets its own seekable handle to the trace by calling `open`.
+fn collect_application_trace<R, F>(trace_size: u64, open: F) -> Result<ApplicationTrace>
+where
+    R: Read + Seek,
+    F: Fn() -> Result<R> + Sync,
+{
+    let num_threads = rayon::current_num_threads();
+    debug!("concurrency level: {num_threads}");
+
+    // skip whatever comes before the event array,
+    // i.e., {"traceEvents":[ or just [
+    let events_start = locate_events(open()?)?;
+    let init_skip = events_start.offset;
 
     // calculate chunksize
-    let chunk_size = (file_size as usize - init_skip + num_threads - 1) / num_threads;
+    let chunk_size = ((trace_size - init_skip).div_ceil(num_threads as u64) as usize).max(1);
 
     // create thread ids
     let threads: Vec<usize> = (0..num_threads).collect();
 
-    let application_trace = threads
+    let mut chunks = threads
         .par_iter()
-        .filter_map(move |thread_id| {
-            read::collect_events(*thread_id, file_path, chunk_size, init_skip as u64).ok()
-        })
-        .map(|events| build_application_trace(events))
+        .map(|thread_id| read::collect_events(*thread_id, &open, chunk_size, init_skip))
+        .collect::<Result<Vec<_>>>()?;
+
+    // each chunk guessed where its first event starts, which is checked against where the
+    // previous chunk stopped
+    align_chunks(&mut chunks, |start_pos, end_pos| {
+        read::read_chunk(open()?, start_pos, end_pos, true)
+    })?;
+    // where the event array is closed, unless none of the chunks saw its end
+    let events_end = chunks
+        .last()
+        .filter(|(_, bounds)| bounds.closed)
+        .map(|(_, bounds)| bounds.next);
+
+    let mut application_trace = chunks
+        .into_par_iter()
+        .map(|(events, _)| build_application_trace(events))
         .reduce(Default::default, |mut first, second| {
-            for (id, mut events) in second.sync_tasks.into_iter() {
-                first.sync_tasks.entry(id).or_default().append(&mut events)
-            }
-            for (id, mut events) in second.async_tasks.into_iter() {
-                first.async_tasks.entry(id).or_default().append(&mut events)
-            }
-            for (id, mut events) in second.object_life_cycle.into_iter() {
-                first
-                    .object_life_cycle
-                    .entry(id)
-                    .or_default()
-                    .append(&mut events)
-            }
+            first.append(second);
             first
         });
 
+    // the fields around the event array are read apart from the events, and so are the events
+    // of the system trace, which are merged into each task by their timestamps
+    let surroundings = read_surroundings(open()?, events_start, events_end)?;
+    if surroundings.system_trace_events.is_some() {
+        application_trace.append(build_application_trace(surroundings.events));
+        application_trace.sort();
+    }
+    application_trace.fields = surroundings.fields;
+
+    application_trace.truncated = events_end.is_none();
+    if application_trace.truncated {
+        warn!("trace is truncated");
+    }
+
     Ok(application_trace)
 }
 
@@ -67,19 +219,46 @@ fn build_application_trace(events: Vec<Event>) -> ApplicationTrace {
     let mut app_trace = ApplicationTrace::new();
     for event in events.into_iter() {
         match event.phase_type {
+            EventPhase::SyncInstant if event.instant_scope != InstantScope::Thread => {
+                app_trace.markers.push(event)
+            }
+            EventPhase::Metadata => app_trace.metadata.push(event),
+            EventPhase::Counter => app_trace.counters.push(event),
+            EventPhase::Sample => app_trace.samples.push(event),
+            EventPhase::Clock => app_trace.clock_syncs.push(event),
+            EventPhase::MemoryDumpGlobal | EventPhase::MemoryDumpProcess => {
+                app_trace.memory.push(event)
+            }
+            EventPhase::FlowStart | EventPhase::FlowStep | EventPhase::FlowEnd => {
+                app_trace.flows.push(event)
+            }
             EventPhase::SyncBegin
             | EventPhase::SyncEnd
             | EventPhase::SyncInstant
-            | EventPhase::Complete => {
+            | EventPhase::Complete
+            | EventPhase::Mark
+            | EventPhase::ContextEnter
+            | EventPhase::ContextLeave => {
                 let id = (event.pid, event.tid);
                 app_trace.sync_tasks.entry(id).or_default().push(event);
             }
-            EventPhase::AsyncBegin | EventPhase::AsyncEnd | EventPhase::AsyncInstant => {
-                let id = (event.scope.clone(), event.id, event.category.clone());
+            EventPhase::AsyncBegin
+            | EventPhase::AsyncEnd
+            | EventPhase::AsyncInstant
+            | EventPhase::AsyncStart
+            | EventPhase::AsyncStepInto
+            | EventPhase::AsyncStepPast
+            | EventPhase::AsyncFinish => {
+                let id = (
+                    event.scope.clone(),
+                    event.id_pid(),
+                    event.id().clone(),
+                    event.category.clone(),
+                );
                 app_trace.async_tasks.entry(id).or_default().push(event);
             }
             EventPhase::ObjectCreate | EventPhase::ObjectSnapshot | EventPhase::ObjectDestroy => {
-                let id = (event.scope.clone(), event.id);
+                let id = (event.scope.clone(), event.id_pid(), event.id().clone());
                 app_trace
                     .object_life_cycle
                     .entry(id)
@@ -91,3 +270,181 @@ fn build_application_trace(events: Vec<Event>) -> ApplicationTrace {
     }
     app_trace
 }
+
+#[cfg(test)]
+mod test {
+    use std::path::Path;
+
+    #[test]
+    fn check_compressed_traces_are_grouped_correctly() -> std::io::Result<()> {
+        let trace_plain = super::collect_traces(Path::new("../data/trace-valid-ending.json"))?;
+        for file_path in [
+            "../data/trace-valid-ending.json.gz",
+            "../data/trace-valid-ending.json.zst",
+        ] {
+            let trace_parallel = rayon::ThreadPoolBuilder::new()
+                .num_threads(4)
+                .build()
+                .unwrap()
+                .install(|| super::collect_traces(Path::new(file_path)))?;
+
+            assert_eq!(trace_plain.sync_tasks, trace_parallel.sync_tasks);
+            assert_eq!(trace_plain.async_tasks, trace_parallel.async_tasks);
+            assert_eq!(
+                trace_plain.object_life_cycle,
+                trace_parallel.object_life_cycle
+            );
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn check_ndjson_traces_are_grouped_correctly() -> std::io::Result<()> {
+        let trace_array = super::collect_traces(Path::new("../data/trace-valid-ending.json"))?;
+        let trace_ndjson = rayon::ThreadPoolBuilder::new()
+            .num_threads(3)
+            .build()
+            .unwrap()
+            .install(|| super::collect_ndjson_traces(Path::new("../data/trace.ndjson")))?;
+
+        assert!(!trace_ndjson.truncated);
+        assert_eq!(trace_array.sync_tasks, trace_ndjson.sync_tasks);
+        assert_eq!(trace_array.async_tasks, trace_ndjson.async_tasks);
+        assert_eq!(
+            trace_array.object_life_cycle,
+            trace_ndjson.object_life_cycle
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn check_truncated_trace_is_reported() -> std::io::Result<()> {
+        let trace = super::collect_traces(Path::new("../data/trace-valid-ending.json"))?;
+        assert!(!trace.truncated);
+        let trace = super::collect_traces(Path::new("../data/trace-array.json"))?;
+        assert!(!trace.truncated);
+        let trace = super::collect_traces(Path::new("../data/trace-truncated.json"))?;
+        assert!(trace.truncated);
+        Ok(())
+    }
+
+    #[test]
+    fn check_any_layout_is_grouped_like_baseline() -> std::io::Result<()> {
+        for file_path in [
+            "../data/trace-minified.json",
+            "../data/trace-pretty.json",
+            "../data/trace-crlf.json",
+            "../data/trace-lenient.json",
+            "../data/trace-fields.json",
+            "../data/trace-systrace.json",
+            "../data/trace-markers.json",
+            "../data/trace-flows.json",
+            "../data/trace-counters.json",
+            "../data/trace-profile.json",
+            "../data/trace-memory.json",
+            "../data/trace-clock.json",
+            "../data/trace-vitals.json",
+            "../data/trace-objects.json",
+        ] {
+            let trace_sync = super::collect_traces(Path::new(file_path))?;
+            for num_threads in [2, 3, 7, 16] {
+                let trace_parallel = rayon::ThreadPoolBuilder::new()
+                    .num_threads(num_threads)
+                    .build()
+                    .unwrap()
+                    .install(|| super::collect_traces(Path::new(file_path)))?;
+
+                assert!(!trace_parallel.truncated);
+                assert_eq!(trace_sync.sync_tasks, trace_parallel.sync_tasks);
+                assert_eq!(trace_sync.async_tasks, trace_parallel.async_tasks);
+                assert_eq!(
+                    trace_sync.object_life_cycle,
+                    trace_parallel.object_life_cycle
+                );
+                assert_eq!(trace_sync.markers, trace_parallel.markers);
+                assert_eq!(trace_sync.fields, trace_parallel.fields);
+                assert_eq!(trace_sync.metadata, trace_parallel.metadata);
+                assert_eq!(trace_sync.flows, trace_parallel.flows);
+                assert_eq!(trace_sync.counters, trace_parallel.counters);
+                assert_eq!(trace_sync.samples, trace_parallel.samples);
+                assert_eq!(trace_sync.memory, trace_parallel.memory);
+                assert_eq!(trace_sync.clock_syncs, trace_parallel.clock_syncs);
+            }
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn check_clocks_are_aligned_like_baseline() -> std::io::Result<()> {
+        let path = Path::new("../data/trace-clock.json");
+        let app_cct = baseline::build_application_cct(baseline::collect_traces(path)?);
+        let parallel_cct = rayon::ThreadPoolBuilder::new()
+            .num_threads(3)
+            .build()
+            .unwrap()
+            .install(|| super::collect_traces(path))?
+            .application_cct();
+
+        assert_eq!(app_cct.clock_sync, parallel_cct.clock_sync);
+        assert_eq!(app_cct.fields.samples, parallel_cct.fields.samples);
+        let spans = |cct: &baseline::CCT| {
+            cct.into_iter()
+                .map(|node| (node.start_time(), node.stop_time()))
+                .collect::<Vec<_>>()
+        };
+        for (id, cct) in &app_cct.sync_tasks {
+            assert_eq!(spans(cct), spans(&parallel_cct.sync_tasks[id]));
+        }
+        for (id, cct) in &app_cct.async_tasks {
+            assert_eq!(spans(cct), spans(&parallel_cct.async_tasks[id]));
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn check_web_vitals_are_reported_like_baseline() -> std::io::Result<()> {
+        let path = Path::new("../data/trace-vitals.json");
+        let app_cct = baseline::build_application_cct(baseline::collect_traces(path)?);
+        let parallel_cct = rayon::ThreadPoolBuilder::new()
+            .num_threads(3)
+            .build()
+            .unwrap()
+            .install(|| super::collect_traces(path))?
+            .application_cct();
+
+        assert_eq!(app_cct.web_vitals.navigations.len(), 3);
+        assert_eq!(app_cct.web_vitals, parallel_cct.web_vitals);
+        Ok(())
+    }
+
+    #[test]
+    fn check_traces_are_grouped_from_memory_and_readers() -> std::io::Result<()> {
+        let trace_file = super::collect_traces(Path::new("../data/trace-pretty.json"))?;
+        let data = std::fs::read("../data/trace-pretty.json")?;
+        let pool = rayon::ThreadPoolBuilder::new()
+            .num_threads(4)
+            .build()
+            .unwrap();
+
+        let traces = [
+            pool.install(|| super::collect_traces_from_slice(&data))?,
+            pool.install(|| super::collect_traces_from_reader(std::io::Cursor::new(&data)))?,
+            pool.install(|| super::collect_traces_from_stream(data.as_slice()))?,
+        ];
+        for trace in traces {
+            assert!(!trace.truncated);
+            assert_eq!(trace_file.sync_tasks, trace.sync_tasks);
+            assert_eq!(trace_file.async_tasks, trace.async_tasks);
+            assert_eq!(trace_file.object_life_cycle, trace.object_life_cycle);
+        }
+
+        let data = std::fs::read("../data/trace.ndjson")?;
+        let trace_ndjson = pool.install(|| super::collect_ndjson_traces_from_slice(&data))?;
+        let trace_array = super::collect_traces(Path::new("../data/trace-valid-ending.json"))?;
+        assert_eq!(trace_array.sync_tasks, trace_ndjson.sync_tasks);
+
+        Ok(())
+    }
+}
@@ -0,0 +1,17 @@
+use baseline::{read_lines, Event};
+use std::io::{Read, Result, Seek};
+
+/// collect_events opens a new handle to a newline delimited trace and reads a chunk of it to
+/// generate events
+pub fn collect_events<R, F>(
+    thread_id: usize,
+    open: &F,
+    chunk_size: usize,
+) -> std::io::Result<(Vec<Event>, bool)>
+where
+    R: Read + Seek,
+    F: Fn() -> Result<R>,
+{
+    let start_pos = (thread_id * chunk_size) as u64;
+    read_lines(open()?, start_pos, start_pos + chunk_size as u64)
+}
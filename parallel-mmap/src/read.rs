@@ -0,0 +1,151 @@
+use baseline::{
+    align_chunks, locate_events, read_surroundings, split_slice, ChunkBounds, Event, Trace,
+};
+use memmap2::Mmap;
+use rayon::prelude::*;
+use std::{
+    fs::File,
+    io::{Cursor, Read, Result},
+    path::Path,
+};
+use utils::Compression;
+
+use log::{debug, warn};
+
+/// extracts all events that start within a chunk of the mapped trace file.
+/// the events are parsed right out of the mapping, without copying them into buffers first.
+fn read_chunk(
+    data: &[u8],
+    start_pos: u64,
+    end_pos: u64,
+    aligned: bool,
+) -> Result<(Vec<Event>, ChunkBounds)> {
+    let mut events = Vec::new();
+    let bounds = split_slice(
+        data,
+        start_pos,
+        end_pos,
+        aligned,
+        |event| match serde_json::from_slice(event) {
+            Ok(event) => events.push(event),
+            Err(e) => warn!(
+                "faced error when parsing {}: {e}",
+                String::from_utf8_lossy(event)
+            ),
+        },
+    )?;
+    Ok((events, bounds))
+}
+
+/// maps the trace file into memory once and split the json parsing of it between threads.
+/// this function creates a Trace of the given file.
+pub fn parallel_mmap(file_path: &Path) -> Result<Trace> {
+    let file = File::open(file_path)?;
+    // SAFETY: the trace file is not expected to be modified while it is being read. if it is,
+    // the events read from it might be garbled, but they are still owned and valid.
+    let mapped = unsafe { Mmap::map(&file)? };
+    parallel_mmap_slice(&mapped)
+}
+
+/// splits the json parsing of a trace that is already in memory between threads.
+pub fn parallel_mmap_slice(data: &[u8]) -> Result<Trace> {
+    match Compression::from_magic(data) {
+        Compression::None => read_trace(data),
+        // compressed data has to be decoded before it can be split
+        _ => read_trace(&utils::decode_to_vec(data)?),
+    }
+}
+
+/// reads a trace from a reader into memory and splits the json parsing of it between threads.
+pub fn parallel_mmap_stream<R: Read>(reader: R) -> Result<Trace> {
+    read_trace(&utils::decode_to_vec(reader)?)
+}
+
+/// splits the json parsing of a trace between threads, each of which parses a chunk of the data.
+fn read_trace(data: &[u8]) -> Result<Trace> {
+    let num_threads = rayon::current_num_threads();
+    debug!("concurrency level: {num_threads}");
+
+    // skip whatever comes before the event array,
+    // i.e., {"traceEvents":[ or just [
+    let events_start = locate_events(data)?;
+    let init_skip = events_start.offset;
+
+    let trace_size = data.len() as u64;
+    let chunk_size = (trace_size - init_skip).div_ceil(num_threads as u64).max(1);
+
+    let threads: Vec<u64> = (0..num_threads as u64).collect();
+
+    let mut chunks = threads
+        .par_iter()
+        .map(|thread_id| {
+            let start_pos = init_skip + thread_id * chunk_size;
+            // only the first chunk is known to start right before an event
+            read_chunk(data, start_pos, start_pos + chunk_size, *thread_id == 0)
+        })
+        .collect::<Result<Vec<_>>>()?;
+
+    // each chunk guessed where its first event starts, which is checked against where the
+    // previous chunk stopped
+    align_chunks(&mut chunks, |start_pos, end_pos| {
+        read_chunk(data, start_pos, end_pos, true)
+    })?;
+
+    // where the event array is closed, unless none of the chunks saw its end
+    let events_end = chunks
+        .last()
+        .filter(|(_, bounds)| bounds.closed)
+        .map(|(_, bounds)| bounds.next);
+    let truncated = events_end.is_none();
+
+    let events: Vec<Event> = chunks.into_iter().flat_map(|(events, _)| events).collect();
+
+    if truncated {
+        warn!("trace is truncated, recovered {} events", events.len());
+    }
+
+    // the fields around the event array are read apart from the events, and so are the events
+    // of the system trace, which are appended after them
+    let mut trace = read_surroundings(Cursor::new(data), events_start, events_end)?;
+    let system_events = std::mem::replace(&mut trace.events, events);
+    if trace.system_trace_events.is_some() {
+        trace.events.extend(system_events);
+    }
+    trace.truncated = truncated;
+    Ok(trace)
+}
+
+#[cfg(test)]
+mod test {
+    use std::path::Path;
+
+    #[test]
+    fn check_mapped_events_match_baseline() -> std::io::Result<()> {
+        for file_path in [
+            "../data/trace-valid-ending.json",
+            "../data/trace-valid-ending.json.zst",
+            "../data/trace-minified.json",
+            "../data/trace-pretty.json",
+            "../data/trace-crlf.json",
+            "../data/trace-lenient.json",
+            "../data/trace-fields.json",
+            "../data/trace-systrace.json",
+            "../data/trace-truncated.json",
+        ] {
+            let trace_sync = baseline::collect_traces(Path::new(file_path))?;
+            for num_threads in [1, 3, 8] {
+                let trace_mapped = rayon::ThreadPoolBuilder::new()
+                    .num_threads(num_threads)
+                    .build()
+                    .unwrap()
+                    .install(|| super::parallel_mmap(Path::new(file_path)))?;
+
+                assert_eq!(trace_sync.truncated, trace_mapped.truncated);
+                assert_eq!(trace_sync.events, trace_mapped.events);
+                assert_eq!(trace_sync.fields, trace_mapped.fields);
+            }
+        }
+
+        Ok(())
+    }
+}
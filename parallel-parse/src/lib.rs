@@ -1,16 +1,31 @@
-use std::io::Result;
+use std::io::{Read, Result, Seek};
 use std::path::Path;
 
 mod read;
 use baseline::ApplicationCCT;
 use baseline::Trace;
-use read::parallel_parse;
+use read::{parallel_parse, parallel_parse_reader, parallel_parse_slice, parallel_parse_stream};
 
 /// collect_traces reads a tracefile and construct a Trace
 pub fn collect_traces(trace_path: &Path) -> Result<Trace> {
     parallel_parse(trace_path)
 }
 
+/// collect_traces_from_slice construct a Trace from a tracefile that is already in memory
+pub fn collect_traces_from_slice(data: &[u8]) -> Result<Trace> {
+    parallel_parse_slice(data)
+}
+
+/// collect_traces_from_reader construct a Trace from the whole content of a seekable reader
+pub fn collect_traces_from_reader<R: Read + Seek + Send>(reader: R) -> Result<Trace> {
+    parallel_parse_reader(reader)
+}
+
+/// collect_traces_from_stream construct a Trace from a reader that cannot seek, e.g. stdin
+pub fn collect_traces_from_stream<R: Read>(reader: R) -> Result<Trace> {
+    parallel_parse_stream(reader)
+}
+
 /// build_application_cct reads the Trace and creates the ApplicationCCT
 /// from the trace
 pub fn build_application_cct(trace: Trace) -> ApplicationCCT {
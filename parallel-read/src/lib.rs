@@ -1,16 +1,60 @@
-use std::io::Result;
+use std::io::{Read, Result, Seek};
 use std::path::Path;
 
+mod ndjson;
 mod read;
 use baseline::ApplicationCCT;
 use baseline::Trace;
-use read::parallel_read;
+use ndjson::{
+    parallel_read_ndjson, parallel_read_ndjson_reader, parallel_read_ndjson_slice,
+    parallel_read_ndjson_stream,
+};
+use read::{parallel_read, parallel_read_reader, parallel_read_slice, parallel_read_stream};
 
 /// collect_traces reads a tracefile and construct a Trace
 pub fn collect_traces(trace_path: &Path) -> Result<Trace> {
     parallel_read(trace_path)
 }
 
+/// collect_traces_from_slice construct a Trace from a tracefile that is already in memory
+pub fn collect_traces_from_slice(data: &[u8]) -> Result<Trace> {
+    parallel_read_slice(data)
+}
+
+/// collect_traces_from_reader construct a Trace from the whole content of a seekable reader
+pub fn collect_traces_from_reader<R: Read + Seek + Send>(reader: R) -> Result<Trace> {
+    parallel_read_reader(reader)
+}
+
+/// collect_traces_from_stream construct a Trace from a reader that cannot seek, e.g. stdin
+pub fn collect_traces_from_stream<R: Read>(reader: R) -> Result<Trace> {
+    parallel_read_stream(reader)
+}
+
+/// collect_ndjson_traces reads a newline delimited tracefile, i.e., one event per line,
+/// and construct a Trace
+pub fn collect_ndjson_traces(trace_path: &Path) -> Result<Trace> {
+    parallel_read_ndjson(trace_path)
+}
+
+/// collect_ndjson_traces_from_slice construct a Trace from a newline delimited tracefile that is
+/// already in memory
+pub fn collect_ndjson_traces_from_slice(data: &[u8]) -> Result<Trace> {
+    parallel_read_ndjson_slice(data)
+}
+
+/// collect_ndjson_traces_from_reader construct a Trace from the whole content of a seekable
+/// reader holding a newline delimited trace
+pub fn collect_ndjson_traces_from_reader<R: Read + Seek + Send>(reader: R) -> Result<Trace> {
+    parallel_read_ndjson_reader(reader)
+}
+
+/// collect_ndjson_traces_from_stream construct a Trace from a reader that cannot seek, e.g.
+/// stdin, holding a newline delimited trace
+pub fn collect_ndjson_traces_from_stream<R: Read>(reader: R) -> Result<Trace> {
+    parallel_read_ndjson_stream(reader)
+}
+
 /// build_application_cct reads the Trace and creates the ApplicationCCT
 /// from the trace
 pub fn build_application_cct(trace: Trace) -> ApplicationCCT {
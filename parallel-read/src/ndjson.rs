@@ -0,0 +1,118 @@
+use baseline::{read_lines, Trace};
+use rayon::prelude::*;
+use std::{
+    fs::File,
+    io::{Cursor, Read, Result, Seek, SeekFrom},
+    path::Path,
+    sync::Mutex,
+};
+use utils::{Compression, SharedReader};
+
+use log::debug;
+
+/// takes a path to a newline delimited trace file, i.e., a file with a complete event on each
+/// line and no surrounding array, and split the loading and json parsing of it between threads.
+/// this function creates a Trace of the given file.
+pub fn parallel_read_ndjson(file_path: &Path) -> Result<Trace> {
+    match Compression::detect(file_path)? {
+        Compression::None => {
+            let file_size = File::open(file_path)?.metadata()?.len();
+            read_trace(file_size, || File::open(file_path))
+        }
+        _ => {
+            let data = utils::decode_to_vec(File::open(file_path)?)?;
+            read_trace(data.len() as u64, || Ok(Cursor::new(data.as_slice())))
+        }
+    }
+}
+
+/// splits the loading and json parsing of a trace that is already in memory between threads.
+pub fn parallel_read_ndjson_slice(data: &[u8]) -> Result<Trace> {
+    match Compression::from_magic(data) {
+        Compression::None => read_trace(data.len() as u64, || Ok(Cursor::new(data))),
+        _ => {
+            let data = utils::decode_to_vec(data)?;
+            read_trace(data.len() as u64, || Ok(Cursor::new(data.as_slice())))
+        }
+    }
+}
+
+/// splits the loading and json parsing of the whole content of a seekable reader between
+/// threads. the threads share the reader, each reading its own chunk of it. their reads are
+/// serialized, so only the json parsing runs in parallel, unlike when reading a file by its path.
+pub fn parallel_read_ndjson_reader<R: Read + Seek + Send>(reader: R) -> Result<Trace> {
+    let mut reader = reader;
+    match Compression::detect_reader(&mut reader)? {
+        Compression::None => {
+            let trace_size = reader.seek(SeekFrom::End(0))?;
+            let source = Mutex::new(reader);
+            read_trace(trace_size, || Ok(SharedReader::new(&source)))
+        }
+        _ => parallel_read_ndjson_stream(reader),
+    }
+}
+
+/// reads a trace from a reader that cannot seek, e.g. stdin, into memory and splits the json
+/// parsing of it between threads.
+pub fn parallel_read_ndjson_stream<R: Read>(reader: R) -> Result<Trace> {
+    let data = utils::decode_to_vec(reader)?;
+    read_trace(data.len() as u64, || Ok(Cursor::new(data.as_slice())))
+}
+
+/// splits the lines of a newline delimited trace between threads.
+/// each thread gets its own seekable handle to the trace by calling `open`.
+fn read_trace<R, F>(trace_size: u64, open: F) -> Result<Trace>
+where
+    R: Read + Seek,
+    F: Fn() -> Result<R> + Sync,
+{
+    let num_threads = rayon::current_num_threads();
+    debug!("concurrency level: {num_threads}");
+
+    // since every line is a complete event, there is no header to skip and each chunk can be
+    // read independently of the others.
+    let chunk_size = trace_size.div_ceil(num_threads as u64) as usize;
+
+    let threads: Vec<usize> = (0..num_threads).collect();
+
+    let (events, truncated) = threads
+        .par_iter()
+        .map(|thread_id| {
+            let start_pos = (thread_id * chunk_size) as u64;
+            read_lines(open()?, start_pos, start_pos + chunk_size as u64)
+        })
+        .try_reduce(
+            || (Vec::new(), false),
+            |(mut this, this_truncated), (mut other, other_truncated)| {
+                this.append(&mut other);
+                Ok((this, this_truncated || other_truncated))
+            },
+        )?;
+
+    Ok(Trace {
+        events,
+        truncated,
+        ..Default::default()
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use std::path::Path;
+
+    #[test]
+    fn check_ndjson_events_match_the_array_reader() -> std::io::Result<()> {
+        let trace_sync = baseline::collect_traces(Path::new("../data/trace-valid-ending.json"))?;
+        for num_threads in [1, 3, 8] {
+            let trace_ndjson = rayon::ThreadPoolBuilder::new()
+                .num_threads(num_threads)
+                .build()
+                .unwrap()
+                .install(|| super::parallel_read_ndjson(Path::new("../data/trace.ndjson")))?;
+
+            assert!(!trace_ndjson.truncated);
+            assert_eq!(trace_sync.events, trace_ndjson.events);
+        }
+        Ok(())
+    }
+}
@@ -1,109 +1,165 @@
-use baseline::{Event, Trace};
+use baseline::{
+    align_chunks, locate_events, read_surroundings, split_chunk, ChunkBounds, Event, Trace,
+};
 use rayon::prelude::*;
 use std::{
     fs::File,
-    io::{BufRead, BufReader, Result, Seek, SeekFrom},
+    io::{Cursor, Read, Result, Seek, SeekFrom},
     path::Path,
+    sync::Mutex,
 };
+use utils::{Compression, SharedReader};
 
 use log::{debug, warn};
 
-/// reads a chunk of the trace file and extract all events from it.
-fn read_chunk(file: File, start_pos: u64, chunk_size: usize) -> Result<Vec<Event>> {
+/// reads a chunk of the trace file and extract all events that start within it.
+fn read_chunk<R: Read + Seek>(
+    source: R,
+    start_pos: u64,
+    end_pos: u64,
+    aligned: bool,
+) -> Result<(Vec<Event>, ChunkBounds)> {
     let mut events = Vec::new();
-    // Seek to the start position assigned to the thread
-    let mut file = file;
-
-    file.seek(SeekFrom::Start(start_pos))?;
-    let mut data = BufReader::new(file);
-
-    // denotes weather we reached the end of event array in json file
-    let mut end_of_events = false;
-
-    let mut bytes_read = 0;
-
-    let mut buf = Vec::with_capacity(1500);
-    while !end_of_events && bytes_read <= chunk_size {
-        if let Ok(mut nbytes @ 1..) = data.read_until(b'\n', &mut buf) {
-            bytes_read += nbytes;
-
-            // according to the chrome's trace json format, the last line of events, ends with the
-            // event object, followed by `]`, followed by the next field in the trace object. so we
-            // need to check if the line ends in a valid index-ending, i.e, a `}` followed by a `,`
-            // or if it ends with ':' showing that the array is finished and the next field of the
-            // trace object is read.
-            if !(&buf[nbytes - 2..nbytes] == b",\n") {
-                let pattern = b"}],";
-                if let Some(pos) = buf
-                    .windows(pattern.len())
-                    .rposition(|window| window == pattern)
-                {
-                    // next line substracts nbytes by 2 to address the trailing ",\n"
-                    nbytes = pos + 3;
-                }
-                end_of_events = true;
-            }
-            nbytes -= 2;
-
-            let event: Event = match serde_json::from_slice(&buf[..nbytes]) {
-                Ok(event) => event,
-                Err(e) => {
-                    warn!(
-                        "faced error when parsing {}: {e}",
-                        String::from_utf8_lossy(&buf[..nbytes])
-                    );
-                    buf.clear();
-                    continue;
-                }
-            };
-            buf.clear();
-            events.push(event);
-        } else {
-            break;
-        }
-    }
-    Ok(events)
+    let bounds =
+        split_chunk(
+            source,
+            start_pos,
+            end_pos,
+            aligned,
+            |event| match serde_json::from_slice(event) {
+                Ok(event) => events.push(event),
+                Err(e) => warn!(
+                    "faced error when parsing {}: {e}",
+                    String::from_utf8_lossy(event)
+                ),
+            },
+        )?;
+    Ok((events, bounds))
 }
 
-/// collect_events gets a trace file path and reads a chunk of it to generate its events
-fn collect_events(
+/// collect_events opens a new handle to the trace and reads a chunk of it to generate its events
+fn collect_events<R, F>(
     thread_id: usize,
-    file_path: &Path,
+    open: &F,
     chunk_size: usize,
     init_skip: u64,
-) -> std::io::Result<Vec<Event>> {
-    let file = File::open(file_path)?;
+) -> std::io::Result<(Vec<Event>, ChunkBounds)>
+where
+    R: Read + Seek,
+    F: Fn() -> Result<R>,
+{
     let start_pos = init_skip + (thread_id * chunk_size) as u64;
-    let events: Vec<Event> = read_chunk(file, start_pos, chunk_size)?;
-    Ok(events)
+    // only the first chunk is known to start right before an event
+    read_chunk(
+        open()?,
+        start_pos,
+        start_pos + chunk_size as u64,
+        thread_id == 0,
+    )
 }
 
 /// takes a path to the trace file and split the loading and json parsing of it between threads.
 /// this function creates a Trace of the given file.
 pub fn parallel_read(file_path: &Path) -> Result<Trace> {
+    match Compression::detect(file_path)? {
+        Compression::None => {
+            let file_size = File::open(file_path)?.metadata()?.len();
+            read_trace(file_size, || File::open(file_path))
+        }
+        _ => {
+            // compressed streams are not seekable, so the trace is decompressed into memory once
+            // and the threads split the decoded bytes between themselves instead.
+            let data = utils::decode_to_vec(File::open(file_path)?)?;
+            read_trace(data.len() as u64, || Ok(Cursor::new(data.as_slice())))
+        }
+    }
+}
+
+/// splits the loading and json parsing of a trace that is already in memory between threads.
+pub fn parallel_read_slice(data: &[u8]) -> Result<Trace> {
+    match Compression::from_magic(data) {
+        Compression::None => read_trace(data.len() as u64, || Ok(Cursor::new(data))),
+        _ => {
+            let data = utils::decode_to_vec(data)?;
+            read_trace(data.len() as u64, || Ok(Cursor::new(data.as_slice())))
+        }
+    }
+}
+
+/// splits the loading and json parsing of the whole content of a seekable reader between
+/// threads. the threads share the reader, each reading its own chunk of it. their reads are
+/// serialized, so only the json parsing runs in parallel, unlike when reading a file by its path.
+pub fn parallel_read_reader<R: Read + Seek + Send>(reader: R) -> Result<Trace> {
+    let mut reader = reader;
+    match Compression::detect_reader(&mut reader)? {
+        Compression::None => {
+            let trace_size = reader.seek(SeekFrom::End(0))?;
+            let source = Mutex::new(reader);
+            read_trace(trace_size, || Ok(SharedReader::new(&source)))
+        }
+        _ => parallel_read_stream(reader),
+    }
+}
+
+/// reads a trace from a reader that cannot seek, e.g. stdin, into memory and splits the json
+/// parsing of it between threads.
+pub fn parallel_read_stream<R: Read>(reader: R) -> Result<Trace> {
+    let data = utils::decode_to_vec(reader)?;
+    read_trace(data.len() as u64, || Ok(Cursor::new(data.as_slice())))
+}
+
+/// splits the loading and json parsing of a trace between threads.
+/// each thread gets its own seekable handle to the trace by calling `open`.
+fn read_trace<R, F>(trace_size: u64, open: F) -> Result<Trace>
+where
+    R: Read + Seek,
+    F: Fn() -> Result<R> + Sync,
+{
     let num_threads = rayon::current_num_threads();
     debug!("concurrency level: {num_threads}");
 
-    let file = File::open(file_path)?;
-    let file_size = file.metadata()?.len();
-
-    // ignore the first line since it represents the trace array,
-    // i.e., {"traceEvents":[
-    let init_skip = BufReader::new(file).read_until(b'\n', &mut vec![]).unwrap();
+    // skip whatever comes before the event array,
+    // i.e., {"traceEvents":[ or just [
+    let events_start = locate_events(open()?)?;
+    let init_skip = events_start.offset;
 
-    let chunk_size = (file_size as usize - init_skip + num_threads - 1) / num_threads;
+    let chunk_size = ((trace_size - init_skip).div_ceil(num_threads as u64) as usize).max(1);
 
     let threads: Vec<usize> = (0..num_threads).collect();
 
-    let events = threads
+    let mut chunks = threads
         .par_iter()
-        .map(move |thread_id| collect_events(*thread_id, file_path, chunk_size, init_skip as u64))
-        .try_reduce(Vec::new, |mut this, mut other| {
-            this.append(&mut other);
-            Ok(this)
-        })?;
+        .map(|thread_id| collect_events(*thread_id, &open, chunk_size, init_skip))
+        .collect::<Result<Vec<_>>>()?;
+
+    // each chunk guessed where its first event starts, which is checked against where the
+    // previous chunk stopped
+    align_chunks(&mut chunks, |start_pos, end_pos| {
+        read_chunk(open()?, start_pos, end_pos, true)
+    })?;
 
-    Ok(Trace { events })
+    // where the event array is closed, unless none of the chunks saw its end
+    let events_end = chunks
+        .last()
+        .filter(|(_, bounds)| bounds.closed)
+        .map(|(_, bounds)| bounds.next);
+    let truncated = events_end.is_none();
+
+    let events: Vec<Event> = chunks.into_iter().flat_map(|(events, _)| events).collect();
+
+    if truncated {
+        warn!("trace is truncated, recovered {} events", events.len());
+    }
+
+    // the fields around the event array are read apart from the events, and so are the events
+    // of the system trace, which are appended after them
+    let mut trace = read_surroundings(open()?, events_start, events_end)?;
+    let system_events = std::mem::replace(&mut trace.events, events);
+    if trace.system_trace_events.is_some() {
+        trace.events.extend(system_events);
+    }
+    trace.truncated = truncated;
+    Ok(trace)
 }
 
 #[cfg(test)]
@@ -124,4 +180,97 @@ mod test {
 
         Ok(())
     }
+
+    #[test]
+    fn check_array_and_truncated_traces_are_read() -> std::io::Result<()> {
+        let trace_object = baseline::collect_traces(Path::new("../data/trace-valid-ending.json"))?;
+        let pool = rayon::ThreadPoolBuilder::new()
+            .num_threads(4)
+            .build()
+            .unwrap();
+
+        let trace_array =
+            pool.install(|| super::parallel_read(Path::new("../data/trace-array.json")))?;
+        assert!(!trace_array.truncated);
+        assert_eq!(trace_object.events, trace_array.events);
+
+        let trace_truncated =
+            pool.install(|| super::parallel_read(Path::new("../data/trace-truncated.json")))?;
+        assert!(trace_truncated.truncated);
+        assert_eq!(trace_object.events[..40], trace_truncated.events[..],);
+
+        Ok(())
+    }
+
+    #[test]
+    fn check_compressed_events_are_read_correctly() -> std::io::Result<()> {
+        let trace_plain = baseline::collect_traces(Path::new("../data/trace-valid-ending.json"))?;
+        for file_path in [
+            "../data/trace-valid-ending.json.gz",
+            "../data/trace-valid-ending.json.zst",
+        ] {
+            let trace_sync = baseline::collect_traces(Path::new(file_path))?;
+            let trace_parallel = rayon::ThreadPoolBuilder::new()
+                .num_threads(4)
+                .build()
+                .unwrap()
+                .install(|| super::parallel_read(Path::new(file_path)))?;
+
+            assert_eq!(trace_plain.events, trace_sync.events);
+            assert_eq!(trace_plain.events, trace_parallel.events);
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn check_any_layout_is_read_like_baseline() -> std::io::Result<()> {
+        for file_path in [
+            "../data/trace-minified.json",
+            "../data/trace-pretty.json",
+            "../data/trace-crlf.json",
+            "../data/trace-lenient.json",
+            "../data/trace-fields.json",
+            "../data/trace-systrace.json",
+        ] {
+            let trace_sync = baseline::collect_traces(Path::new(file_path))?;
+            for num_threads in [2, 3, 7, 16] {
+                let trace_parallel = rayon::ThreadPoolBuilder::new()
+                    .num_threads(num_threads)
+                    .build()
+                    .unwrap()
+                    .install(|| super::parallel_read(Path::new(file_path)))?;
+
+                assert!(!trace_parallel.truncated);
+                assert_eq!(trace_sync.events, trace_parallel.events);
+                assert_eq!(trace_sync.fields, trace_parallel.fields);
+            }
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn check_traces_are_read_from_memory_and_readers() -> std::io::Result<()> {
+        let trace_sync = baseline::collect_traces(Path::new("../data/trace-pretty.json"))?;
+        let pool = rayon::ThreadPoolBuilder::new()
+            .num_threads(4)
+            .build()
+            .unwrap();
+
+        let data = std::fs::read("../data/trace-pretty.json")?;
+        let trace_slice = pool.install(|| super::parallel_read_slice(&data))?;
+        assert_eq!(trace_sync.events, trace_slice.events);
+
+        let reader = std::io::Cursor::new(data.as_slice());
+        let trace_reader = pool.install(|| super::parallel_read_reader(reader))?;
+        assert_eq!(trace_sync.events, trace_reader.events);
+
+        let stream = std::fs::File::open("../data/trace-valid-ending.json.gz")?;
+        let trace_stream = pool.install(|| super::parallel_read_stream(stream))?;
+        let trace_file = baseline::collect_traces(Path::new("../data/trace-valid-ending.json"))?;
+        assert_eq!(trace_file.events, trace_stream.events);
+
+        Ok(())
+    }
 }
@@ -0,0 +1,40 @@
+use std::fs::File;
+use std::io::{Read, Result, Seek};
+use std::path::Path;
+
+mod proto;
+mod read;
+use baseline::ApplicationCCT;
+use baseline::Trace;
+use read::decode_trace;
+
+/// collect_traces reads a perfetto protobuf tracefile and construct a Trace
+pub fn collect_traces(trace_path: &Path) -> Result<Trace> {
+    let data = utils::decode_to_vec(File::open(trace_path)?)?;
+    decode_trace(&data)
+}
+
+/// collect_traces_from_slice construct a Trace from a perfetto protobuf tracefile that is already
+/// in memory
+pub fn collect_traces_from_slice(data: &[u8]) -> Result<Trace> {
+    match utils::Compression::from_magic(data) {
+        utils::Compression::None => decode_trace(data),
+        _ => decode_trace(&utils::decode_to_vec(data)?),
+    }
+}
+
+/// collect_traces_from_reader construct a Trace from the whole content of a seekable reader
+pub fn collect_traces_from_reader<R: Read + Seek + Send>(reader: R) -> Result<Trace> {
+    collect_traces_from_stream(reader)
+}
+
+/// collect_traces_from_stream construct a Trace from a reader that cannot seek, e.g. stdin
+pub fn collect_traces_from_stream<R: Read>(reader: R) -> Result<Trace> {
+    decode_trace(&utils::decode_to_vec(reader)?)
+}
+
+/// build_application_cct reads the Trace and creates the ApplicationCCT
+/// from the trace
+pub fn build_application_cct(trace: Trace) -> ApplicationCCT {
+    baseline::build_application_cct(trace)
+}
@@ -0,0 +1,138 @@
+use std::io::{Error, ErrorKind, Result};
+
+/// Value is a single field value as it is encoded on the protobuf wire.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Value<'a> {
+    Varint(u64),
+    Fixed64(u64),
+    Bytes(&'a [u8]),
+    Fixed32(u32),
+}
+
+impl<'a> Value<'a> {
+    /// interprets the value as an unsigned integer regardless of its encoding.
+    pub fn as_u64(&self) -> u64 {
+        match *self {
+            Value::Varint(v) | Value::Fixed64(v) => v,
+            Value::Fixed32(v) => v as u64,
+            Value::Bytes(_) => 0,
+        }
+    }
+
+    /// interprets the value as a signed integer, i.e., an `int32` or `int64` field.
+    pub fn as_i64(&self) -> i64 {
+        self.as_u64() as i64
+    }
+
+    /// interprets the value as a `double` field.
+    pub fn as_f64(&self) -> f64 {
+        match *self {
+            Value::Fixed64(v) => f64::from_bits(v),
+            Value::Fixed32(v) => f32::from_bits(v) as f64,
+            Value::Varint(v) => v as f64,
+            Value::Bytes(_) => 0.0,
+        }
+    }
+
+    /// interprets the value as a `bytes` or embedded message field.
+    pub fn as_bytes(&self) -> &'a [u8] {
+        match *self {
+            Value::Bytes(bytes) => bytes,
+            _ => &[],
+        }
+    }
+
+    /// interprets the value as a `string` field.
+    pub fn as_string(&self) -> String {
+        String::from_utf8_lossy(self.as_bytes()).into_owned()
+    }
+
+    /// interprets the value as a repeated integer field, which might be packed.
+    pub fn as_packed_u64(&self) -> Result<Vec<u64>> {
+        match *self {
+            Value::Bytes(mut bytes) => {
+                let mut values = Vec::new();
+                while !bytes.is_empty() {
+                    values.push(read_varint(&mut bytes)?);
+                }
+                Ok(values)
+            }
+            value => Ok(vec![value.as_u64()]),
+        }
+    }
+}
+
+/// Message iterates over the fields of an encoded protobuf message.
+pub struct Message<'a> {
+    data: &'a [u8],
+}
+
+impl<'a> Message<'a> {
+    pub fn new(data: &'a [u8]) -> Self {
+        Self { data }
+    }
+
+    fn next_field(&mut self) -> Result<(u32, Value<'a>)> {
+        let key = read_varint(&mut self.data)?;
+        let field = (key >> 3) as u32;
+        let value = match key & 0x7 {
+            0 => Value::Varint(read_varint(&mut self.data)?),
+            1 => Value::Fixed64(u64::from_le_bytes(
+                take(&mut self.data, 8)?.try_into().unwrap(),
+            )),
+            2 => {
+                let len = read_varint(&mut self.data)? as usize;
+                Value::Bytes(take(&mut self.data, len)?)
+            }
+            5 => Value::Fixed32(u32::from_le_bytes(
+                take(&mut self.data, 4)?.try_into().unwrap(),
+            )),
+            wire_type => return Err(malformed(&format!("unsupported wire type {wire_type}"))),
+        };
+        Ok((field, value))
+    }
+}
+
+impl<'a> Iterator for Message<'a> {
+    type Item = Result<(u32, Value<'a>)>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.data.is_empty() {
+            return None;
+        }
+        let field = self.next_field();
+        if field.is_err() {
+            // do not keep on decoding garbage
+            self.data = &[];
+        }
+        Some(field)
+    }
+}
+
+fn read_varint(data: &mut &[u8]) -> Result<u64> {
+    let mut value = 0;
+    for shift in (0..64).step_by(7) {
+        let (byte, rest) = data
+            .split_first()
+            .ok_or_else(|| malformed("varint ends abruptly"))?;
+        *data = rest;
+        value |= ((byte & 0x7f) as u64) << shift;
+        if byte & 0x80 == 0 {
+            return Ok(value);
+        }
+    }
+    Err(malformed("varint is too long"))
+}
+
+fn take<'a>(data: &mut &'a [u8], len: usize) -> Result<&'a [u8]> {
+    if data.len() < len {
+        return Err(malformed("field ends abruptly"));
+    }
+    let (value, rest) = data.split_at(len);
+    *data = rest;
+    Ok(value)
+}
+
+fn malformed(msg: &str) -> Error {
+    Error::new(ErrorKind::InvalidData, format!("malformed protobuf: {msg}"))
+}
@@ -0,0 +1,677 @@
+use std::{collections::HashMap, io::Result};
+
+use baseline::{Event, EventPhase, Id, IdValue, ProcessId, ThreadId, Trace};
+use log::{debug, warn};
+use serde_json::{Map, Value as Json};
+
+use crate::proto::Message;
+
+/// field numbers of the perfetto protos that take part in building events.
+/// see protos/perfetto/trace/ in the perfetto repository for their definitions.
+mod field {
+    pub const TRACE_PACKET: u32 = 1;
+
+    pub const PACKET_TIMESTAMP: u32 = 8;
+    pub const PACKET_SEQUENCE_ID: u32 = 10;
+    pub const PACKET_TRACK_EVENT: u32 = 11;
+    pub const PACKET_INTERNED_DATA: u32 = 12;
+    pub const PACKET_SEQUENCE_FLAGS: u32 = 13;
+    pub const PACKET_INCREMENTAL_STATE_CLEARED: u32 = 41;
+    pub const PACKET_THREAD_DESCRIPTOR: u32 = 44;
+    pub const PACKET_DEFAULTS: u32 = 59;
+    pub const PACKET_TRACK_DESCRIPTOR: u32 = 60;
+
+    pub const DEFAULTS_TRACK_EVENT: u32 = 11;
+    pub const TRACK_EVENT_DEFAULTS_TRACK_UUID: u32 = 11;
+
+    pub const EVENT_CATEGORY_IIDS: u32 = 3;
+    pub const EVENT_DEBUG_ANNOTATIONS: u32 = 4;
+    pub const EVENT_LEGACY_EVENT: u32 = 6;
+    pub const EVENT_TYPE: u32 = 9;
+    pub const EVENT_NAME_IID: u32 = 10;
+    pub const EVENT_TRACK_UUID: u32 = 11;
+    pub const EVENT_CATEGORIES: u32 = 22;
+    pub const EVENT_NAME: u32 = 23;
+    pub const EVENT_COUNTER_VALUE: u32 = 30;
+    pub const EVENT_DOUBLE_COUNTER_VALUE: u32 = 44;
+
+    pub const LEGACY_NAME_IID: u32 = 1;
+    pub const LEGACY_PHASE: u32 = 2;
+    pub const LEGACY_DURATION_US: u32 = 3;
+    pub const LEGACY_UNSCOPED_ID: u32 = 6;
+    pub const LEGACY_ID_SCOPE: u32 = 7;
+    pub const LEGACY_LOCAL_ID: u32 = 10;
+    pub const LEGACY_GLOBAL_ID: u32 = 11;
+    pub const LEGACY_PID_OVERRIDE: u32 = 18;
+    pub const LEGACY_TID_OVERRIDE: u32 = 19;
+
+    pub const ANNOTATION_NAME_IID: u32 = 1;
+    pub const ANNOTATION_BOOL: u32 = 2;
+    pub const ANNOTATION_UINT: u32 = 3;
+    pub const ANNOTATION_INT: u32 = 4;
+    pub const ANNOTATION_DOUBLE: u32 = 5;
+    pub const ANNOTATION_STRING: u32 = 6;
+    pub const ANNOTATION_POINTER: u32 = 7;
+    pub const ANNOTATION_LEGACY_JSON: u32 = 9;
+    pub const ANNOTATION_NAME: u32 = 10;
+    pub const ANNOTATION_DICT_ENTRIES: u32 = 11;
+    pub const ANNOTATION_ARRAY_VALUES: u32 = 12;
+
+    pub const INTERNED_EVENT_CATEGORIES: u32 = 1;
+    pub const INTERNED_EVENT_NAMES: u32 = 2;
+    pub const INTERNED_ANNOTATION_NAMES: u32 = 3;
+    pub const INTERNED_IID: u32 = 1;
+    pub const INTERNED_NAME: u32 = 2;
+
+    pub const TRACK_UUID: u32 = 1;
+    pub const TRACK_NAME: u32 = 2;
+    pub const TRACK_PROCESS: u32 = 3;
+    pub const TRACK_THREAD: u32 = 4;
+    pub const TRACK_PARENT_UUID: u32 = 5;
+    pub const TRACK_COUNTER: u32 = 8;
+
+    pub const PROCESS_PID: u32 = 1;
+    pub const PROCESS_NAME: u32 = 6;
+
+    pub const THREAD_PID: u32 = 1;
+    pub const THREAD_TID: u32 = 2;
+    pub const THREAD_NAME: u32 = 5;
+}
+
+/// TrackEvent.Type
+const TYPE_SLICE_BEGIN: u64 = 1;
+const TYPE_SLICE_END: u64 = 2;
+const TYPE_INSTANT: u64 = 3;
+const TYPE_COUNTER: u64 = 4;
+
+/// TracePacket.SequenceFlags.SEQ_INCREMENTAL_STATE_CLEARED
+const SEQ_INCREMENTAL_STATE_CLEARED: u64 = 1;
+
+/// Track holds what the TrackDescriptors tell about a track.
+#[derive(Debug, Default)]
+struct Track {
+    name: String,
+    parent: Option<u64>,
+    pid: Option<ProcessId>,
+    tid: Option<ThreadId>,
+    counter: bool,
+}
+
+/// Sequence holds the incremental state of a packet sequence, i.e., the strings interned on it
+/// and the defaults of its packets.
+#[derive(Debug, Default)]
+struct Sequence {
+    names: HashMap<u64, String>,
+    categories: HashMap<u64, String>,
+    annotation_names: HashMap<u64, String>,
+    default_track: Option<u64>,
+}
+
+/// PendingEvent is an event whose pid and tid are known only after all tracks are described.
+struct PendingEvent {
+    track: Option<u64>,
+    /// the phase comes from a legacy event and should not be derived from the track.
+    legacy: bool,
+    counter_value: Option<Json>,
+    event: Event,
+}
+
+/// Decoder turns the packets of a perfetto trace into the events of the trace event format.
+#[derive(Default)]
+struct Decoder {
+    tracks: HashMap<u64, Track>,
+    sequences: HashMap<u64, Sequence>,
+    pending: Vec<PendingEvent>,
+}
+
+/// decodes a protobuf perfetto trace into a Trace.
+/// slices on thread tracks become sync events of their thread, slices on any other track become
+/// async events identified by their track, and track and process descriptors become metadata
+/// events.
+pub fn decode_trace(data: &[u8]) -> Result<Trace> {
+    let mut decoder = Decoder::default();
+    for field in Message::new(data) {
+        match field? {
+            (field::TRACE_PACKET, packet) => decoder.packet(packet.as_bytes())?,
+            (field, _) => debug!("ignored trace field {field}"),
+        }
+    }
+    let events = decoder.events();
+    Ok(Trace {
+        events,
+        ..Default::default()
+    })
+}
+
+impl Decoder {
+    fn packet(&mut self, packet: &[u8]) -> Result<()> {
+        let mut timestamp = 0;
+        let mut sequence_id = 0;
+        let mut track_event = None;
+        let mut interned_data = None;
+        let mut defaults = None;
+        let mut thread_descriptor = None;
+        let mut clear_state = false;
+
+        for field in Message::new(packet) {
+            match field? {
+                (field::PACKET_TIMESTAMP, value) => timestamp = value.as_u64(),
+                (field::PACKET_SEQUENCE_ID, value) => sequence_id = value.as_u64(),
+                (field::PACKET_TRACK_EVENT, value) => track_event = Some(value.as_bytes()),
+                (field::PACKET_INTERNED_DATA, value) => interned_data = Some(value.as_bytes()),
+                (field::PACKET_DEFAULTS, value) => defaults = Some(value.as_bytes()),
+                (field::PACKET_SEQUENCE_FLAGS, value) => {
+                    clear_state |= value.as_u64() & SEQ_INCREMENTAL_STATE_CLEARED != 0
+                }
+                (field::PACKET_INCREMENTAL_STATE_CLEARED, value) => {
+                    clear_state |= value.as_u64() != 0
+                }
+                (field::PACKET_TRACK_DESCRIPTOR, value) => {
+                    self.track_descriptor(value.as_bytes())?
+                }
+                (field::PACKET_THREAD_DESCRIPTOR, value) => {
+                    thread_descriptor = Some(value.as_bytes())
+                }
+                _ => (),
+            }
+        }
+
+        if let Some(thread_descriptor) = thread_descriptor {
+            // legacy thread descriptors make the thread the default track of the sequence
+            let uuid = u64::MAX - sequence_id;
+            self.tracks.insert(uuid, thread_track(thread_descriptor)?);
+            self.sequences.entry(sequence_id).or_default().default_track = Some(uuid);
+        }
+
+        if clear_state {
+            let sequence = self.sequences.entry(sequence_id).or_default();
+            sequence.names.clear();
+            sequence.categories.clear();
+            sequence.annotation_names.clear();
+        }
+        if let Some(defaults) = defaults {
+            let default_track = packet_default_track(defaults)?;
+            if default_track.is_some() {
+                self.sequences.entry(sequence_id).or_default().default_track = default_track;
+            }
+        }
+        if let Some(interned_data) = interned_data {
+            self.interned_data(sequence_id, interned_data)?;
+        }
+        if let Some(track_event) = track_event {
+            self.track_event(sequence_id, timestamp as i64, track_event)?;
+        }
+        Ok(())
+    }
+
+    fn track_descriptor(&mut self, descriptor: &[u8]) -> Result<()> {
+        let mut uuid = 0;
+        let mut track = Track::default();
+        for field in Message::new(descriptor) {
+            match field? {
+                (field::TRACK_UUID, value) => uuid = value.as_u64(),
+                (field::TRACK_NAME, value) => track.name = value.as_string(),
+                (field::TRACK_PARENT_UUID, value) => track.parent = Some(value.as_u64()),
+                (field::TRACK_COUNTER, _) => track.counter = true,
+                (field::TRACK_PROCESS, value) => {
+                    for field in Message::new(value.as_bytes()) {
+                        match field? {
+                            (field::PROCESS_PID, value) => track.pid = Some(value.as_i64() as _),
+                            (field::PROCESS_NAME, value) => track.name = value.as_string(),
+                            _ => (),
+                        }
+                    }
+                }
+                (field::TRACK_THREAD, value) => {
+                    let thread = thread_track(value.as_bytes())?;
+                    track.pid = thread.pid;
+                    track.tid = thread.tid;
+                    if !thread.name.is_empty() {
+                        track.name = thread.name;
+                    }
+                }
+                _ => (),
+            }
+        }
+        self.tracks.insert(uuid, track);
+        Ok(())
+    }
+
+    fn interned_data(&mut self, sequence_id: u64, interned_data: &[u8]) -> Result<()> {
+        let sequence = self.sequences.entry(sequence_id).or_default();
+        for field in Message::new(interned_data) {
+            let (field, value) = field?;
+            let table = match field {
+                field::INTERNED_EVENT_CATEGORIES => &mut sequence.categories,
+                field::INTERNED_EVENT_NAMES => &mut sequence.names,
+                field::INTERNED_ANNOTATION_NAMES => &mut sequence.annotation_names,
+                _ => continue,
+            };
+            let mut iid = 0;
+            let mut name = String::new();
+            for field in Message::new(value.as_bytes()) {
+                match field? {
+                    (field::INTERNED_IID, value) => iid = value.as_u64(),
+                    (field::INTERNED_NAME, value) => name = value.as_string(),
+                    _ => (),
+                }
+            }
+            table.insert(iid, name);
+        }
+        Ok(())
+    }
+
+    fn track_event(&mut self, sequence_id: u64, timestamp: i64, track_event: &[u8]) -> Result<()> {
+        let sequence = self.sequences.entry(sequence_id).or_default();
+        let mut pending = PendingEvent {
+            track: sequence.default_track,
+            legacy: false,
+            counter_value: None,
+            event: Event {
+                timestamp,
+                phase_type: EventPhase::SyncInstant,
+                ..Default::default()
+            },
+        };
+        let event = &mut pending.event;
+        let mut categories = Vec::new();
+        let mut args = Map::new();
+
+        for field in Message::new(track_event) {
+            match field? {
+                (field::EVENT_TYPE, value) => {
+                    event.phase_type = match value.as_u64() {
+                        TYPE_SLICE_BEGIN => EventPhase::SyncBegin,
+                        TYPE_SLICE_END => EventPhase::SyncEnd,
+                        TYPE_INSTANT => EventPhase::SyncInstant,
+                        TYPE_COUNTER => EventPhase::Counter,
+                        kind => {
+                            warn!("track event of unknown type {kind} is ignored");
+                            return Ok(());
+                        }
+                    }
+                }
+                (field::EVENT_TRACK_UUID, value) => pending.track = Some(value.as_u64()),
+                (field::EVENT_NAME, value) => event.name = value.as_string(),
+                (field::EVENT_NAME_IID, value) => {
+                    event.name = sequence
+                        .names
+                        .get(&value.as_u64())
+                        .cloned()
+                        .unwrap_or_default()
+                }
+                (field::EVENT_CATEGORIES, value) => categories.push(value.as_string()),
+                (field::EVENT_CATEGORY_IIDS, value) => {
+                    for iid in value.as_packed_u64()? {
+                        categories.extend(sequence.categories.get(&iid).cloned());
+                    }
+                }
+                (field::EVENT_DEBUG_ANNOTATIONS, value) => {
+                    let (name, value) = debug_annotation(sequence, value.as_bytes())?;
+                    args.insert(name, value);
+                }
+                (field::EVENT_COUNTER_VALUE, value) => {
+                    pending.counter_value = Some(Json::from(value.as_i64()))
+                }
+                (field::EVENT_DOUBLE_COUNTER_VALUE, value) => {
+                    pending.counter_value = Some(Json::from(value.as_f64()))
+                }
+                (field::EVENT_LEGACY_EVENT, value) => {
+                    pending.legacy = true;
+                    legacy_event(sequence, event, value.as_bytes())?;
+                }
+                _ => (),
+            }
+        }
+
+        event.category = categories.join(",");
+        if !args.is_empty() {
+            event.args = Some(Json::Object(args));
+        }
+        self.pending.push(pending);
+        Ok(())
+    }
+
+    /// resolves the tracks of the pending events and creates the metadata events of the tracks.
+    fn events(mut self) -> Vec<Event> {
+        let mut events = Vec::with_capacity(self.pending.len() + self.tracks.len());
+
+        for track in self.tracks.values() {
+            let (name, tid) = match track.tid {
+                Some(tid) => ("thread_name", tid),
+                None if track.parent.is_none() && track.pid.is_some() => ("process_name", 0),
+                None => continue,
+            };
+            if track.name.is_empty() {
+                continue;
+            }
+            events.push(Event {
+                name: name.into(),
+                category: "__metadata".into(),
+                phase_type: EventPhase::Metadata,
+                pid: track.pid.unwrap_or_default(),
+                tid,
+                args: Some(serde_json::json!({ "name": track.name })),
+                ..Default::default()
+            });
+        }
+        events.sort_by_key(|event| (event.pid, event.tid));
+
+        // slice ends usually carry neither name nor category, so they are taken from the slice
+        // begin they close on the same track.
+        let mut open_slices: HashMap<u64, Vec<(String, String)>> = HashMap::new();
+
+        let mut pending = std::mem::take(&mut self.pending);
+        pending.sort_by_key(|pending| pending.event.timestamp);
+        for PendingEvent {
+            track,
+            legacy,
+            counter_value,
+            mut event,
+        } in pending
+        {
+            let uuid = track.unwrap_or_default();
+            let track = self.tracks.get(&uuid);
+            let pid = self.process_of(uuid);
+
+            match event.phase_type {
+                EventPhase::SyncBegin => open_slices
+                    .entry(uuid)
+                    .or_default()
+                    .push((event.name.clone(), event.category.clone())),
+                EventPhase::SyncEnd => {
+                    if let Some((name, category)) =
+                        open_slices.get_mut(&uuid).and_then(|slices| slices.pop())
+                    {
+                        if event.name.is_empty() {
+                            event.name = name;
+                        }
+                        if event.category.is_empty() {
+                            event.category = category;
+                        }
+                    }
+                }
+                _ => (),
+            }
+
+            match track {
+                // slices of a thread belong to the sync task of the thread
+                Some(Track { tid: Some(tid), .. }) => {
+                    if event.pid == 0 {
+                        event.pid = pid.unwrap_or_default();
+                    }
+                    if event.tid == 0 {
+                        event.tid = *tid;
+                    }
+                }
+                Some(track) if track.counter => {
+                    event.pid = pid.unwrap_or_default();
+                    if event.name.is_empty() {
+                        event.name = track.name.clone();
+                    }
+                    let mut args = Map::new();
+                    args.insert(track.name.clone(), counter_value.unwrap_or_default());
+                    event.args = Some(Json::Object(args));
+                }
+                // slices of any other track are async operations identified by their track
+                Some(_) if !legacy => {
+                    event.pid = pid.unwrap_or_default();
+                    event.id = Id::Global(IdValue::Int(uuid));
+                    event.phase_type = match event.phase_type {
+                        EventPhase::SyncBegin => EventPhase::AsyncBegin,
+                        EventPhase::SyncEnd => EventPhase::AsyncEnd,
+                        _ => EventPhase::AsyncInstant,
+                    };
+                }
+                Some(_) if event.pid == 0 => event.pid = pid.unwrap_or_default(),
+                Some(_) => (),
+                None if legacy => (),
+                None => {
+                    warn!(
+                        "event {} on undescribed track {uuid} is ignored",
+                        event.name
+                    );
+                    continue;
+                }
+            }
+            events.push(event);
+        }
+        events
+    }
+
+    /// walks up the track hierarchy to find the process a track belongs to.
+    fn process_of(&self, mut uuid: u64) -> Option<ProcessId> {
+        while let Some(track) = self.tracks.get(&uuid) {
+            if track.pid.is_some() {
+                return track.pid;
+            }
+            uuid = track.parent?;
+        }
+        None
+    }
+}
+
+/// decodes a ThreadDescriptor into a thread track.
+fn thread_track(descriptor: &[u8]) -> Result<Track> {
+    let mut track = Track::default();
+    for field in Message::new(descriptor) {
+        match field? {
+            (field::THREAD_PID, value) => track.pid = Some(value.as_i64() as _),
+            (field::THREAD_TID, value) => track.tid = Some(value.as_i64() as _),
+            (field::THREAD_NAME, value) => track.name = value.as_string(),
+            _ => (),
+        }
+    }
+    Ok(track)
+}
+
+/// extracts the default track uuid from a TracePacketDefaults.
+fn packet_default_track(defaults: &[u8]) -> Result<Option<u64>> {
+    let mut track = None;
+    for field in Message::new(defaults) {
+        if let (field::DEFAULTS_TRACK_EVENT, value) = field? {
+            for field in Message::new(value.as_bytes()) {
+                if let (field::TRACK_EVENT_DEFAULTS_TRACK_UUID, value) = field? {
+                    track = Some(value.as_u64());
+                }
+            }
+        }
+    }
+    Ok(track)
+}
+
+/// applies a TrackEvent.LegacyEvent to the event, legacy events carry the json phase and ids
+/// that have no counterpart in typed track events, e.g. object snapshots.
+fn legacy_event(sequence: &Sequence, event: &mut Event, legacy: &[u8]) -> Result<()> {
+    for field in Message::new(legacy) {
+        match field? {
+            (field::LEGACY_NAME_IID, value) => {
+                event.name = sequence
+                    .names
+                    .get(&value.as_u64())
+                    .cloned()
+                    .unwrap_or_default()
+            }
+            (field::LEGACY_PHASE, value) => {
+                let phase = char::from_u32(value.as_u64() as u32).unwrap_or_default();
+                event.phase_type = EventPhase::from_code(&phase.to_string());
+            }
+            (field::LEGACY_DURATION_US, value) => {
+                event.duration = Some(value.as_i64() * utils::NANOS_PER_MICRO)
+            }
+            (field::LEGACY_UNSCOPED_ID | field::LEGACY_GLOBAL_ID, value) => {
+                event.id = Id::Global(IdValue::Int(value.as_u64()))
+            }
+            (field::LEGACY_LOCAL_ID, value) => event.id = Id::Local(IdValue::Int(value.as_u64())),
+            (field::LEGACY_ID_SCOPE, value) => event.scope = value.as_string(),
+            (field::LEGACY_PID_OVERRIDE, value) => event.pid = value.as_i64() as _,
+            (field::LEGACY_TID_OVERRIDE, value) => event.tid = value.as_i64() as _,
+            _ => (),
+        }
+    }
+    Ok(())
+}
+
+/// decodes a DebugAnnotation into a named json value.
+fn debug_annotation(sequence: &Sequence, annotation: &[u8]) -> Result<(String, Json)> {
+    let mut name = String::new();
+    let mut value = Json::Null;
+    let mut dict = Map::new();
+    let mut array = Vec::new();
+    for field in Message::new(annotation) {
+        match field? {
+            (field::ANNOTATION_NAME, v) => name = v.as_string(),
+            (field::ANNOTATION_NAME_IID, v) => {
+                name = sequence
+                    .annotation_names
+                    .get(&v.as_u64())
+                    .cloned()
+                    .unwrap_or_default()
+            }
+            (field::ANNOTATION_BOOL, v) => value = Json::from(v.as_u64() != 0),
+            (field::ANNOTATION_UINT, v) => value = Json::from(v.as_u64()),
+            (field::ANNOTATION_INT, v) => value = Json::from(v.as_i64()),
+            (field::ANNOTATION_DOUBLE, v) => value = Json::from(v.as_f64()),
+            (field::ANNOTATION_STRING, v) => value = Json::from(v.as_string()),
+            (field::ANNOTATION_POINTER, v) => value = Json::from(format!("0x{:x}", v.as_u64())),
+            (field::ANNOTATION_LEGACY_JSON, v) => {
+                value = serde_json::from_slice(v.as_bytes()).unwrap_or(Json::Null)
+            }
+            (field::ANNOTATION_DICT_ENTRIES, v) => {
+                let (name, value) = debug_annotation(sequence, v.as_bytes())?;
+                dict.insert(name, value);
+            }
+            (field::ANNOTATION_ARRAY_VALUES, v) => {
+                array.push(debug_annotation(sequence, v.as_bytes())?.1)
+            }
+            _ => (),
+        }
+    }
+    if !dict.is_empty() {
+        value = Json::Object(dict);
+    } else if !array.is_empty() {
+        value = Json::Array(array);
+    }
+    Ok((name, value))
+}
+
+#[cfg(test)]
+mod test {
+    use baseline::{build_application_cct, Event};
+
+    use super::decode_trace;
+
+    /// encodes a protobuf field. numbers are encoded as varints and slices as length delimited
+    /// fields.
+    enum Field<'a> {
+        Int(u32, u64),
+        Bytes(u32, &'a [u8]),
+    }
+
+    fn varint(mut value: u64, buf: &mut Vec<u8>) {
+        while value >= 0x80 {
+            buf.push((value as u8) | 0x80);
+            value >>= 7;
+        }
+        buf.push(value as u8);
+    }
+
+    fn message(fields: &[Field]) -> Vec<u8> {
+        let mut buf = Vec::new();
+        for field in fields {
+            match field {
+                Field::Int(number, value) => {
+                    varint((*number as u64) << 3, &mut buf);
+                    varint(*value, &mut buf);
+                }
+                Field::Bytes(number, bytes) => {
+                    varint(((*number as u64) << 3) | 2, &mut buf);
+                    varint(bytes.len() as u64, &mut buf);
+                    buf.extend_from_slice(bytes);
+                }
+            }
+        }
+        buf
+    }
+
+    fn packet(timestamp: u64, track_event: &[Field]) -> Vec<u8> {
+        message(&[
+            Field::Int(8, timestamp),
+            Field::Int(10, 7),
+            Field::Bytes(11, &message(track_event)),
+        ])
+    }
+
+    #[test]
+    fn check_track_events_are_decoded() -> std::io::Result<()> {
+        use Field::*;
+
+        let process = message(&[Int(1, 10), Bytes(6, b"Browser")]);
+        let thread = message(&[Int(1, 10), Int(2, 11), Bytes(5, b"CrBrowserMain")]);
+        let interned = message(&[
+            Bytes(1, &message(&[Int(1, 1), Bytes(2, b"toplevel")])),
+            Bytes(2, &message(&[Int(1, 1), Bytes(2, b"RunTask")])),
+        ]);
+        let object = message(&[Int(2, b'N' as u64), Int(6, 0x700)]);
+        let packets = [
+            message(&[Bytes(60, &message(&[Int(1, 1), Bytes(3, &process)]))]),
+            message(&[Bytes(
+                60,
+                &message(&[Int(1, 2), Int(5, 1), Bytes(4, &thread)]),
+            )]),
+            message(&[Bytes(
+                60,
+                &message(&[Int(1, 3), Int(5, 1), Bytes(2, b"Load")]),
+            )]),
+            message(&[Int(10, 7), Int(13, 1), Bytes(12, &interned)]),
+            packet(1_000_000, &[Int(9, 1), Int(11, 2), Int(10, 1), Int(3, 1)]),
+            packet(
+                1_000_500,
+                &[Int(11, 2), Bytes(23, b"Document"), Bytes(6, &object)],
+            ),
+            packet(
+                1_005_000,
+                &[
+                    Int(9, 3),
+                    Int(11, 2),
+                    Bytes(23, b"Schedule"),
+                    Bytes(22, b"toplevel"),
+                    Bytes(4, &message(&[Bytes(10, b"n"), Int(4, 3)])),
+                ],
+            ),
+            packet(
+                1_010_000,
+                &[Int(9, 1), Int(11, 3), Bytes(23, b"Load"), Bytes(22, b"net")],
+            ),
+            packet(1_020_000, &[Int(9, 2), Int(11, 2)]),
+            packet(1_030_000, &[Int(9, 2), Int(11, 3)]),
+        ];
+        let trace: Vec<u8> = packets
+            .iter()
+            .flat_map(|p| message(&[Bytes(1, p)]))
+            .collect();
+
+        let expected: Vec<Event> = serde_json::from_str(
+            r#"[
+            {"name":"process_name","cat":"__metadata","ph":"M","pid":10,"tid":0,"ts":0,"args":{"name":"Browser"}},
+            {"name":"thread_name","cat":"__metadata","ph":"M","pid":10,"tid":11,"ts":0,"args":{"name":"CrBrowserMain"}},
+            {"name":"RunTask","cat":"toplevel","ph":"B","pid":10,"tid":11,"ts":1000},
+            {"name":"Document","cat":"","ph":"N","id":"0x700","pid":10,"tid":11,"ts":1000.5},
+            {"name":"Schedule","cat":"toplevel","ph":"i","pid":10,"tid":11,"ts":1005,"args":{"n":3}},
+            {"name":"Load","cat":"net","ph":"b","id":3,"pid":10,"tid":0,"ts":1010},
+            {"name":"RunTask","cat":"toplevel","ph":"E","pid":10,"tid":11,"ts":1020},
+            {"name":"Load","cat":"net","ph":"e","id":3,"pid":10,"tid":0,"ts":1030}
+            ]"#,
+        )
+        .unwrap();
+
+        let trace = decode_trace(&trace)?;
+        assert_eq!(expected, trace.events);
+
+        let app_cct = build_application_cct(trace);
+        assert_eq!(app_cct.sync_tasks.len(), 1);
+        assert_eq!(app_cct.async_tasks.len(), 1);
+        assert_eq!(app_cct.object_life_cycle.len(), 1);
+        Ok(())
+    }
+}
@@ -34,7 +34,7 @@ cargo build -p experiment --release
 # threads=(1 2 4 8 16 32)
 threads=(1 2 4 8)
 traces=($(ls $TRACE_DIRECTORY))
-modes=("baseline" "parallel-read" "parallel-parse" "parallel-cct")
+modes=("baseline" "parallel-read" "parallel-parse" "parallel-cct" "parallel-mmap")
 
 for num_threads in "${threads[@]}"
 do
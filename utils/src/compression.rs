@@ -0,0 +1,77 @@
+use std::{
+    fs::File,
+    io::{BufReader, Cursor, Read, Result, Seek},
+    path::Path,
+};
+
+use flate2::read::MultiGzDecoder;
+
+const GZIP_MAGIC: [u8; 2] = [0x1f, 0x8b];
+const ZSTD_MAGIC: [u8; 4] = [0x28, 0xb5, 0x2f, 0xfd];
+
+/// Compression denotes how the bytes of a tracefile are encoded on disk.
+/// it is detected from the magic bytes at the start of the file, so file extensions
+/// do not matter.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Compression {
+    None,
+    Gzip,
+    Zstd,
+}
+
+impl Compression {
+    /// detects the compression from the first bytes of the data.
+    pub fn from_magic(magic: &[u8]) -> Self {
+        if magic.starts_with(&GZIP_MAGIC) {
+            Compression::Gzip
+        } else if magic.starts_with(&ZSTD_MAGIC) {
+            Compression::Zstd
+        } else {
+            Compression::None
+        }
+    }
+
+    /// detects the compression of the file at the given path.
+    pub fn detect(path: &Path) -> Result<Self> {
+        let (_, magic) = peek_magic(File::open(path)?)?;
+        Ok(Compression::from_magic(&magic))
+    }
+
+    /// detects the compression of a seekable reader and rewinds it to its start.
+    pub fn detect_reader<R: Read + Seek>(reader: &mut R) -> Result<Self> {
+        reader.rewind()?;
+        let (_, magic) = peek_magic(reader.by_ref())?;
+        reader.rewind()?;
+        Ok(Compression::from_magic(&magic))
+    }
+}
+
+/// reads as many bytes as a magic number needs without losing them, i.e., the returned reader
+/// still yields the peeked bytes.
+fn peek_magic<R: Read>(mut reader: R) -> Result<(impl Read, Vec<u8>)> {
+    let mut magic = Vec::with_capacity(ZSTD_MAGIC.len());
+    reader
+        .by_ref()
+        .take(ZSTD_MAGIC.len() as u64)
+        .read_to_end(&mut magic)?;
+    Ok((Cursor::new(magic.clone()).chain(reader), magic))
+}
+
+/// decode wraps the reader in the decoder matching its content.
+/// uncompressed data is passed through as is.
+pub fn decode<'a, R: Read + 'a>(reader: R) -> Result<Box<dyn Read + 'a>> {
+    let (reader, magic) = peek_magic(reader)?;
+    let decoded: Box<dyn Read> = match Compression::from_magic(&magic) {
+        Compression::None => Box::new(reader),
+        Compression::Gzip => Box::new(MultiGzDecoder::new(BufReader::new(reader))),
+        Compression::Zstd => Box::new(zstd::Decoder::new(reader)?),
+    };
+    Ok(decoded)
+}
+
+/// decodes the entire content of the reader into memory.
+pub fn decode_to_vec<R: Read>(reader: R) -> Result<Vec<u8>> {
+    let mut data = Vec::new();
+    decode(reader)?.read_to_end(&mut data)?;
+    Ok(data)
+}
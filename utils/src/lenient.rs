@@ -0,0 +1,181 @@
+use core::fmt;
+
+use serde::{
+    de::{self, Visitor},
+    Deserialize, Deserializer,
+};
+
+/// deserialize an integer that might be given as a float, a string or null.
+/// strings that are not numbers, e.g. the `"pid": "Browser"` of some exporters, are hashed so
+/// that equal strings still yield equal integers.
+pub fn de_lenient_int<'de, D>(deserializer: D) -> Result<i64, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    deserializer.deserialize_any(LenientIntVisitor)
+}
+
+/// deserialize a string that might be given as a number, a boolean or null.
+pub fn de_lenient_string<'de, D>(deserializer: D) -> Result<String, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    deserializer.deserialize_any(LenientStringVisitor)
+}
+
+/// deserialize a string that might be missing, where null and the empty string are missing too.
+pub fn de_lenient_opt_string<'de, D>(deserializer: D) -> Result<Option<String>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    let v = de_lenient_string(deserializer)?;
+    Ok(Some(v).filter(|v| !v.is_empty()))
+}
+
+/// deserialize a list of strings whose items might be given as numbers, where null is an empty
+/// list.
+pub fn de_lenient_strings<'de, D>(deserializer: D) -> Result<Vec<String>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    let items = Option::<Vec<LenientString>>::deserialize(deserializer)?;
+    Ok(items.into_iter().flatten().map(|item| item.0).collect())
+}
+
+struct LenientString(String);
+
+impl<'de> Deserialize<'de> for LenientString {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        de_lenient_string(deserializer).map(LenientString)
+    }
+}
+
+/// hashes a string into a non negative integer with FNV-1a, which is stable across runs.
+pub fn hash_to_int(v: &str) -> i64 {
+    let hash = v.bytes().fold(0xcbf29ce484222325_u64, |hash, byte| {
+        (hash ^ byte as u64).wrapping_mul(0x100000001b3)
+    });
+    (hash >> 1) as i64
+}
+
+struct LenientIntVisitor;
+
+impl Visitor<'_> for LenientIntVisitor {
+    type Value = i64;
+
+    fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        formatter.write_str("an integer")
+    }
+
+    fn visit_unit<E>(self) -> Result<Self::Value, E>
+    where
+        E: de::Error,
+    {
+        Ok(0)
+    }
+
+    fn visit_bool<E>(self, v: bool) -> Result<Self::Value, E>
+    where
+        E: de::Error,
+    {
+        Ok(v as i64)
+    }
+
+    fn visit_i64<E>(self, v: i64) -> Result<Self::Value, E>
+    where
+        E: de::Error,
+    {
+        Ok(v)
+    }
+
+    fn visit_u64<E>(self, v: u64) -> Result<Self::Value, E>
+    where
+        E: de::Error,
+    {
+        // saturates like a float that is too large does
+        Ok(i64::try_from(v).unwrap_or(i64::MAX))
+    }
+
+    fn visit_f64<E>(self, v: f64) -> Result<Self::Value, E>
+    where
+        E: de::Error,
+    {
+        Ok(v as i64)
+    }
+
+    fn visit_str<E>(self, v: &str) -> Result<Self::Value, E>
+    where
+        E: de::Error,
+    {
+        let trimmed = v.trim();
+        match trimmed.parse::<i64>() {
+            Ok(v) => Ok(v),
+            Err(_) => match trimmed.parse::<f64>() {
+                Ok(v) => self.visit_f64(v),
+                Err(_) => Ok(hash_to_int(v)),
+            },
+        }
+    }
+}
+
+struct LenientStringVisitor;
+
+impl Visitor<'_> for LenientStringVisitor {
+    type Value = String;
+
+    fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        formatter.write_str("a string")
+    }
+
+    fn visit_unit<E>(self) -> Result<Self::Value, E>
+    where
+        E: de::Error,
+    {
+        Ok(String::new())
+    }
+
+    fn visit_bool<E>(self, v: bool) -> Result<Self::Value, E>
+    where
+        E: de::Error,
+    {
+        Ok(v.to_string())
+    }
+
+    fn visit_i64<E>(self, v: i64) -> Result<Self::Value, E>
+    where
+        E: de::Error,
+    {
+        Ok(v.to_string())
+    }
+
+    fn visit_u64<E>(self, v: u64) -> Result<Self::Value, E>
+    where
+        E: de::Error,
+    {
+        Ok(v.to_string())
+    }
+
+    fn visit_f64<E>(self, v: f64) -> Result<Self::Value, E>
+    where
+        E: de::Error,
+    {
+        Ok(v.to_string())
+    }
+
+    fn visit_str<E>(self, v: &str) -> Result<Self::Value, E>
+    where
+        E: de::Error,
+    {
+        Ok(v.into())
+    }
+
+    fn visit_string<E>(self, v: String) -> Result<Self::Value, E>
+    where
+        E: de::Error,
+    {
+        Ok(v)
+    }
+}
@@ -0,0 +1,83 @@
+use std::{
+    io::{Error, ErrorKind, Read, Result, Seek, SeekFrom},
+    sync::Mutex,
+};
+
+/// SharedReader is a handle to a seekable source that is shared between threads.
+/// each handle keeps its own position and moves the source there before every read, so the
+/// threads can read different parts of the source as if each one had opened it.
+/// the source is locked for every read though, so the reads of all handles are serialized, i.e.,
+/// the i/o is sequential and only what the threads do with the data they read runs in parallel.
+pub struct SharedReader<'a, R> {
+    source: &'a Mutex<R>,
+    pos: u64,
+}
+
+impl<'a, R> SharedReader<'a, R> {
+    /// creates a handle positioned at the start of the source.
+    pub fn new(source: &'a Mutex<R>) -> Self {
+        Self { source, pos: 0 }
+    }
+}
+
+impl<R> SharedReader<'_, R> {
+    fn lock(&self) -> Result<std::sync::MutexGuard<'_, R>> {
+        self.source
+            .lock()
+            .map_err(|_| Error::other("shared reader is poisoned"))
+    }
+}
+
+impl<R: Read + Seek> Read for SharedReader<'_, R> {
+    fn read(&mut self, buf: &mut [u8]) -> Result<usize> {
+        let nbytes = {
+            let mut source = self.lock()?;
+            source.seek(SeekFrom::Start(self.pos))?;
+            source.read(buf)?
+        };
+        self.pos += nbytes as u64;
+        Ok(nbytes)
+    }
+}
+
+impl<R: Seek> Seek for SharedReader<'_, R> {
+    fn seek(&mut self, pos: SeekFrom) -> Result<u64> {
+        self.pos = match pos {
+            SeekFrom::Start(offset) => offset,
+            SeekFrom::Current(delta) => self.pos.checked_add_signed(delta).ok_or_else(|| {
+                Error::new(ErrorKind::InvalidInput, "seek to a negative position")
+            })?,
+            SeekFrom::End(_) => self.lock()?.seek(pos)?,
+        };
+        Ok(self.pos)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::{
+        io::{Cursor, Read, Seek, SeekFrom},
+        sync::Mutex,
+    };
+
+    use super::SharedReader;
+
+    #[test]
+    fn check_handles_keep_their_own_position() -> std::io::Result<()> {
+        let source = Mutex::new(Cursor::new(b"0123456789".to_vec()));
+        let mut first = SharedReader::new(&source);
+        let mut second = SharedReader::new(&source);
+
+        second.seek(SeekFrom::Start(6))?;
+        let mut buf = [0; 3];
+        first.read_exact(&mut buf)?;
+        assert_eq!(&buf, b"012");
+        second.read_exact(&mut buf)?;
+        assert_eq!(&buf, b"678");
+        first.read_exact(&mut buf)?;
+        assert_eq!(&buf, b"345");
+
+        assert_eq!(first.seek(SeekFrom::End(-1))?, 9);
+        Ok(())
+    }
+}
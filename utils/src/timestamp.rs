@@ -0,0 +1,119 @@
+use core::fmt;
+
+use serde::{
+    de::{self, Visitor},
+    Deserializer,
+};
+
+/// number of nanoseconds in a microsecond, the unit of the timestamps in the trace event format.
+pub const NANOS_PER_MICRO: i64 = 1000;
+
+/// deserialize a timestamp given in microseconds, either as an integer or a fraction of it,
+/// into nanoseconds
+pub fn de_micros_to_nanos<'de, D>(deserializer: D) -> Result<i64, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    deserializer.deserialize_any(MicrosVisitor)
+}
+
+/// deserialize an optional timestamp given in microseconds into nanoseconds
+pub fn de_opt_micros_to_nanos<'de, D>(deserializer: D) -> Result<Option<i64>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    deserializer.deserialize_option(OptionalMicrosVisitor)
+}
+
+/// converts microseconds into nanoseconds, keeping up to three fractional digits.
+pub fn micros_to_nanos(micros: f64) -> i64 {
+    (micros * NANOS_PER_MICRO as f64).round() as i64
+}
+
+/// denotes whether microseconds given as a fraction fit in nanoseconds.
+pub fn fits_nanos(micros: f64) -> bool {
+    (micros * NANOS_PER_MICRO as f64).abs() < i64::MAX as f64
+}
+
+struct MicrosVisitor;
+
+impl Visitor<'_> for MicrosVisitor {
+    type Value = i64;
+
+    fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        formatter.write_str("a number of microseconds")
+    }
+
+    fn visit_i64<E>(self, v: i64) -> Result<Self::Value, E>
+    where
+        E: de::Error,
+    {
+        v.checked_mul(NANOS_PER_MICRO)
+            .ok_or_else(|| E::custom("timestamp out of range"))
+    }
+
+    fn visit_u64<E>(self, v: u64) -> Result<Self::Value, E>
+    where
+        E: de::Error,
+    {
+        i64::try_from(v)
+            .ok()
+            .and_then(|v| v.checked_mul(NANOS_PER_MICRO))
+            .ok_or_else(|| E::custom("timestamp out of range"))
+    }
+
+    fn visit_f64<E>(self, v: f64) -> Result<Self::Value, E>
+    where
+        E: de::Error,
+    {
+        if !fits_nanos(v) {
+            return Err(E::custom("timestamp out of range"));
+        }
+        Ok(micros_to_nanos(v))
+    }
+
+    fn visit_str<E>(self, v: &str) -> Result<Self::Value, E>
+    where
+        E: de::Error,
+    {
+        // some tracers quote their timestamps
+        match v.trim().parse::<i64>() {
+            Ok(micros) => self.visit_i64(micros),
+            Err(_) => match v.trim().parse::<f64>() {
+                Ok(micros) => self.visit_f64(micros),
+                Err(_) => Err(E::custom(format!("invalid timestamp: {v}"))),
+            },
+        }
+    }
+}
+
+struct OptionalMicrosVisitor;
+
+impl<'de> Visitor<'de> for OptionalMicrosVisitor {
+    type Value = Option<i64>;
+
+    fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        formatter.write_str("a number of microseconds")
+    }
+
+    fn visit_none<E>(self) -> Result<Self::Value, E>
+    where
+        E: de::Error,
+    {
+        Ok(None)
+    }
+
+    fn visit_unit<E>(self) -> Result<Self::Value, E>
+    where
+        E: de::Error,
+    {
+        Ok(None)
+    }
+
+    fn visit_some<D>(self, deserializer: D) -> Result<Self::Value, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        de_micros_to_nanos(deserializer).map(Some)
+    }
+}